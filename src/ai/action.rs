@@ -1,30 +1,205 @@
-use std::sync::mpsc;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
 
+use base64::Engine;
+use futures::{FutureExt, StreamExt, future::BoxFuture};
 use rig::{
-    agent::Agent,
+    OneOrMany,
+    agent::{Agent, MultiTurnStreamItem},
     client::{builder::DynClientBuilder, completion::CompletionModelHandle},
-    completion::Prompt,
-    providers::gemini::completion::gemini_api_types::{AdditionalParameters, GenerationConfig},
+    message::{ContentFormat, ImageMediaType, Message, UserContent},
+    streaming::StreamingPrompt,
 };
 use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::builtin::Builtin;
+use super::cache::{Cache, Slot};
+use super::command::{self, Invocation, command_response};
+use super::detect::{Condition, Detection, Matcher};
+use super::executor::JobId;
+use super::params::GenerationParams;
+use super::pipeline::{Step, run_pipeline};
+use super::provider::{self, Provider};
+use super::retry::{self, Failure, Fallback};
+use super::template::Template;
+use super::usage::{Account, Ledger, Meter};
+use crate::clipboard;
+
+// what an action runs on
+#[derive(Clone, Default)]
+pub(crate) struct Input {
+    pub text: Option<String>,
+    pub html: Option<String>,
+    pub rtf: Option<String>,
+    pub image: Option<clipboard::Image>,
+    // contents of the copied files
+    pub files: Option<String>,
+    // what was in the clipboard before the current text
+    pub previous_text: Option<String>,
+    // values entered in the form of builtins that take arguments
+    pub args: HashMap<String, String>,
+    // ask the model again even if the response is cached
+    pub refresh: bool,
+}
+
+impl Input {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_none() && self.html.is_none() && self.image.is_none() && self.files.is_none()
+    }
+
+    // the text in the requested format, falling back to plain text if not available
+    pub fn text_as(&self, format: &InputFormat) -> Option<String> {
+        let formatted = match format {
+            InputFormat::Plain => None,
+            InputFormat::Html => self.html.clone(),
+            InputFormat::Markdown => self.html.as_deref().map(html2md::parse_html),
+            InputFormat::Rtf => self.rtf.clone(),
+        };
+        formatted.or_else(|| self.text.clone())
+    }
+}
+
+#[derive(Deserialize, Default, Clone, PartialEq)]
+pub(crate) enum InputFormat {
+    #[default]
+    #[serde(rename = "plain")]
+    Plain,
+    #[serde(rename = "html")]
+    Html,
+    #[serde(rename = "markdown")]
+    Markdown,
+    #[serde(rename = "rtf")]
+    Rtf,
+}
+
+impl InputFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InputFormat::Plain => "plain",
+            InputFormat::Html => "html",
+            InputFormat::Markdown => "markdown",
+            InputFormat::Rtf => "rtf",
+        }
+    }
+}
 
 pub(crate) enum Event {
-    Response(String, bool),
-    Error(anyhow::Error),
+    // a piece of the response as it is streamed from the model
+    Chunk(JobId, String),
+    // the complete response, sent once the stream finished cleanly
+    Response(JobId, String, bool),
+    // an intermediate step of a pipeline completed, with its label and output
+    Step(JobId, String, String),
+    // the response that follows comes from the cache
+    Cached(JobId),
+    // a model is asked, with its provider, anything streamed by a previous attempt is
+    // discarded
+    Attempt(JobId, String),
+    // something to tell the user that doesn't stop the job, like a soft budget exceeded
+    Warning(JobId, String),
+    Error(JobId, anyhow::Error),
+}
+
+// what actions need from the rest of the configuration to compile
+pub(crate) struct Context<'a> {
+    pub vars: &'a HashMap<String, String>,
+    // system preamble for actions that don't define their own, none to disable it
+    pub preamble: Option<&'a str>,
+    pub providers: &'a HashMap<String, Provider>,
+    pub cache: &'a Cache,
+    pub ledger: &'a Ledger,
 }
 
 #[derive(Deserialize)]
 pub(crate) struct Action {
     pub label: String,
+    // prompt, model and provider are only optional for pipelines, commands and builtins
+    #[serde(default)]
     pub prompt: String,
     pub key: Option<String>,
+    #[serde(default)]
     pub model: String,
+    #[serde(default)]
     pub provider: String,
     #[serde(default = "default_paste")]
     pub paste: bool,
+    // if true the response is shown as a diff of the text and only pasted once accepted
+    #[serde(default)]
+    pub review: bool,
+    // if true responses are cached on disk and reused when the same request is made
+    #[serde(default)]
+    pub cache: bool,
+    // if true the clipboard image, if any, is sent to the model along with the text
+    #[serde(default)]
+    pub images: bool,
+    // which clipboard format the text is taken from
+    #[serde(default)]
+    pub format: InputFormat,
+    // if true the contents of copied text files are used instead of the clipboard text
+    #[serde(default)]
+    pub files: bool,
+    // system preamble for this action only, empty to disable it
+    pub system: Option<String>,
+    #[serde(flatten)]
+    pub params: GenerationParams,
+    // if set this action is a pipeline, each step gets the previous step's output
+    #[serde(default)]
+    pub steps: Vec<Step>,
+    // only show this action when the clipboard content matches any of these
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    // if set the text is piped into this shell command instead, its output is the result
+    pub command: Option<String>,
+    // seconds the command can run before it's killed, or the model can go without
+    // sending anything before it's given up on
+    pub timeout: Option<u64>,
+    // times the model is asked again after a rate limit, a server error or a timeout
+    pub retries: Option<u32>,
+    // models asked in order when the action's one fails
+    #[serde(default)]
+    pub fallback: Vec<Fallback>,
+    // environment variables passed to the command on top of the basic ones
+    #[serde(default)]
+    pub env: Vec<String>,
+    // if set this local transform runs instead, synchronously
+    pub builtin: Option<Builtin>,
+    // arguments of the builtin, the missing ones are asked for when triggered
+    #[serde(default)]
+    pub args: HashMap<String, String>,
 
     #[serde(skip)]
-    agent: Option<Agent<CompletionModelHandle<'static>>>,
+    template: Option<Template>,
+    #[serde(skip)]
+    vars: HashMap<String, String>,
+    // one per api key of the provider
+    #[serde(skip)]
+    agents: Vec<Agent<CompletionModelHandle<'static>>>,
+    #[serde(skip)]
+    next_agent: AtomicUsize,
+    // the compiled steps of a pipeline
+    #[serde(skip)]
+    stages: Vec<Arc<Action>>,
+    #[serde(skip)]
+    matchers: Vec<Matcher>,
+    #[serde(skip)]
+    response_cache: Option<Cache>,
+    // what identifies a request besides the prompt
+    #[serde(skip)]
+    cache_scope: Value,
+    // where the completions of this action are recorded
+    #[serde(skip)]
+    account: Option<Account>,
+    // the compiled fallback models
+    #[serde(skip)]
+    fallbacks: Vec<Action>,
 }
 
 fn default_paste() -> bool {
@@ -32,74 +207,593 @@ fn default_paste() -> bool {
 }
 
 impl Action {
-    pub fn compile(&mut self) -> anyhow::Result<()> {
-        let mut builder = DynClientBuilder::new()
-            .agent(&self.provider, &self.model)?
-            .preamble(include_str!("system.md"));
+    fn render_prompt(&self, text: Option<&str>, input: &Input) -> anyhow::Result<String> {
+        let template = self
+            .template
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("action not compiled"))?;
+
+        Ok(template.render_prompt(
+            &self.vars,
+            text,
+            input.previous_text.as_deref(),
+            Some(chrono::Local::now().naive_local()),
+        ))
+    }
 
-        // handle google provider
-        if self.provider == "google" {
-            self.provider = "gemini".to_string();
+    pub(super) fn build_message(&self, input: &Input) -> anyhow::Result<Message> {
+        let files = if self.files {
+            input.files.as_ref()
+        } else {
+            None
+        };
+        // when files were copied the text is just their paths, no need to send it
+        let text = match files {
+            Some(files) => Some(files.clone()),
+            None => input.text_as(&self.format),
+        };
+        let prompt = self.render_prompt(text.as_deref(), input)?;
+
+        let image = if self.images {
+            input.image.as_ref()
+        } else {
+            None
+        };
+        if let Some(image) = image {
+            let data = base64::engine::general_purpose::STANDARD.encode(image.png.as_slice());
+            let content = OneOrMany::many(vec![
+                UserContent::text(prompt),
+                UserContent::image(
+                    data,
+                    Some(ContentFormat::Base64),
+                    Some(ImageMediaType::PNG),
+                    None,
+                ),
+            ])?;
+            Ok(Message::User { content })
+        } else if text.is_some() {
+            Ok(Message::from(prompt))
+        } else if input.image.is_some() {
+            Err(anyhow::anyhow!("{} does not accept images", self.label))
+        } else {
+            Err(anyhow::anyhow!("no input text for {}", self.label))
         }
+    }
+
+    pub fn is_pipeline(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    pub fn is_command(&self) -> bool {
+        self.command.is_some()
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        self.builtin.is_some()
+    }
 
-        if self.provider == "gemini" {
-            // if not passed gemini will return an error
-            let gen_config = GenerationConfig::default();
-            let config =
-                serde_json::to_value(AdditionalParameters::default().with_config(gen_config))?;
+    // true if the response comes in chunks, only models stream
+    pub fn streams(&self) -> bool {
+        !self.is_pipeline() && !self.is_command() && !self.is_builtin()
+    }
+
+    // arguments of the builtin that the action doesn't set
+    pub fn missing_args(&self) -> Vec<&'static str> {
+        self.builtin
+            .map(|builtin| builtin.args())
+            .unwrap_or_default()
+            .iter()
+            .filter(|arg| !self.args.contains_key(**arg))
+            .copied()
+            .collect()
+    }
+
+    // the text commands and builtins work on
+    fn local_text(&self, input: &Input) -> anyhow::Result<String> {
+        let text = match input.files.as_ref() {
+            Some(files) if self.files => Some(files.clone()),
+            _ => input.text_as(&self.format),
+        };
+        text.ok_or_else(|| anyhow::anyhow!("{} needs text", self.label))
+    }
+
+    // the command of this action set to run on the input, none for other actions
+    pub(super) fn invocation(&self, input: &Input) -> anyhow::Result<Option<Invocation>> {
+        let Some(command) = self.command.as_ref() else {
+            return Ok(None);
+        };
 
-            println!("gen_config: {:?}", config);
+        Ok(Some(Invocation {
+            command: command.clone(),
+            text: self.local_text(input)?,
+            timeout: Duration::from_secs(self.timeout.unwrap_or(command::DEFAULT_TIMEOUT)),
+            env: self.env.clone(),
+        }))
+    }
 
-            builder = builder.additional_params(config);
+    // run the builtin of this action on the input, none for other actions
+    pub(super) fn run_builtin(&self, input: &Input) -> Option<anyhow::Result<String>> {
+        let builtin = self.builtin?;
+        let mut args = self.args.clone();
+        args.extend(input.args.clone());
+        Some(
+            self.local_text(input)
+                .and_then(|text| builtin.apply(&text, &args)),
+        )
+    }
+
+    // a copy of the settings of this action, without anything compiled
+    fn definition(&self) -> Action {
+        Action {
+            label: self.label.clone(),
+            prompt: self.prompt.clone(),
+            key: None,
+            model: self.model.clone(),
+            provider: self.provider.clone(),
+            paste: self.paste,
+            review: false,
+            cache: false,
+            images: self.images,
+            format: self.format.clone(),
+            files: self.files,
+            system: self.system.clone(),
+            params: self.params.clone(),
+            steps: Vec::new(),
+            when: Vec::new(),
+            command: self.command.clone(),
+            timeout: self.timeout,
+            retries: self.retries,
+            fallback: self.fallback.clone(),
+            env: self.env.clone(),
+            builtin: self.builtin,
+            args: self.args.clone(),
+            template: None,
+            vars: HashMap::new(),
+            agents: Vec::new(),
+            next_agent: AtomicUsize::new(0),
+            stages: Vec::new(),
+            matchers: Vec::new(),
+            response_cache: None,
+            cache_scope: Value::Null,
+            account: None,
+            fallbacks: Vec::new(),
+        }
+    }
+
+    // turn the steps of this pipeline into actions, steps refer to other actions by label
+    // so this needs all of them
+    pub fn resolve_stages(&self, actions: &[Action]) -> anyhow::Result<Vec<Action>> {
+        let mut stages = Vec::new();
+        for (idx, step) in self.steps.iter().enumerate() {
+            let stage = match step {
+                Step::Action { action } => {
+                    let other = actions
+                        .iter()
+                        .find(|other| other.label.eq_ignore_ascii_case(action))
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("step {}: no action labeled {}", idx + 1, action)
+                        })?;
+                    if other.is_pipeline() {
+                        return Err(anyhow::anyhow!(
+                            "step {}: {} is a pipeline, pipelines can't be nested",
+                            idx + 1,
+                            other.label
+                        ));
+                    }
+                    other.definition()
+                }
+                Step::Prompt {
+                    prompt,
+                    model,
+                    provider,
+                    system,
+                    params,
+                } => {
+                    let mut stage = self.definition();
+                    stage.label = format!("step {}", idx + 1);
+                    stage.prompt = prompt.clone();
+                    stage.model = model.clone().unwrap_or_else(|| self.model.clone());
+                    stage.provider = provider.clone().unwrap_or_else(|| self.provider.clone());
+                    stage.system = system.clone().or_else(|| self.system.clone());
+                    stage.params = params.as_ref().clone();
+                    if stage.model.is_empty() || stage.provider.is_empty() {
+                        return Err(anyhow::anyhow!(
+                            "step {}: model and provider must be set on the step or the pipeline",
+                            idx + 1
+                        ));
+                    }
+                    stage
+                }
+            };
+            stages.push(stage);
         }
+        Ok(stages)
+    }
 
-        self.agent = Some(builder.build());
+    pub fn compile_stages(
+        &mut self,
+        mut stages: Vec<Action>,
+        context: &Context,
+    ) -> anyhow::Result<()> {
+        for stage in stages.iter_mut() {
+            stage
+                .compile(context)
+                .map_err(|e| anyhow::anyhow!("pipeline {}: {}", self.label, e))?;
+            // what they cost is part of the pipeline
+            stage.account_to(&self.label);
+        }
+        self.stages = stages.into_iter().map(Arc::new).collect();
         Ok(())
     }
 
+    // true if this action should be shown for the given clipboard text
+    pub fn is_visible(&self, detection: Option<&Detection>, text: &str) -> bool {
+        self.matchers.is_empty()
+            || self
+                .matchers
+                .iter()
+                .any(|matcher| matcher.matches(detection, text))
+    }
+
+    pub fn compile(&mut self, context: &Context) -> anyhow::Result<()> {
+        self.matchers = self
+            .when
+            .iter()
+            .map(|condition| condition.compile())
+            .collect::<anyhow::Result<_>>()
+            .map_err(|e| anyhow::anyhow!("action {}: {}", self.label, e))?;
+
+        // pipelines run their stages, compiled by compile_stages, commands and builtins
+        // need no model
+        if self.is_pipeline() || self.is_command() || self.is_builtin() {
+            return Ok(());
+        }
+
+        // catch template errors now rather than when the action is triggered
+        let template = Template::parse(&self.prompt)
+            .and_then(|template| template.validate(context.vars).map(|_| template))
+            .map_err(|e| anyhow::anyhow!("action {}: {}", self.label, e))?;
+        self.template = Some(template);
+        self.vars = context.vars.clone();
+
+        // named endpoints and known providers, other rig provider ids are configured
+        // from the environment
+        let (builders, backend) = match context.providers.get(&self.provider) {
+            Some(provider) => (
+                provider
+                    .agents(&self.model)
+                    .map_err(|e| anyhow::anyhow!("action {}: {}", self.label, e))?,
+                provider.backend.as_str().to_string(),
+            ),
+            // rig panics when their variables are missing, the action fails when run instead
+            None if !provider::missing_env_vars(&self.provider).is_empty() => {
+                (Vec::new(), self.provider.clone())
+            }
+            None => (
+                vec![DynClientBuilder::new().agent(&self.provider, &self.model)?],
+                self.provider.clone(),
+            ),
+        };
+
+        let preamble = match self.system.as_deref() {
+            Some(system) => Some(system),
+            None => context.preamble,
+        };
+        let additional_params = self.params.additional_params(&backend);
+
+        if self.cache && context.cache.is_enabled() {
+            self.response_cache = Some(context.cache.clone());
+            self.cache_scope = json!({
+                "provider": self.provider,
+                "model": self.model,
+                "preamble": preamble,
+                "temperature": self.params.temperature,
+                "max_tokens": self.params.max_tokens,
+                "params": additional_params,
+            });
+        }
+
+        self.account = Some(Account {
+            ledger: context.ledger.clone(),
+            action: self.label.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+        });
+
+        self.agents = builders
+            .into_iter()
+            .map(|mut builder| {
+                if let Some(preamble) = preamble
+                    && !preamble.trim().is_empty()
+                {
+                    builder = builder.preamble(preamble);
+                }
+                if let Some(temperature) = self.params.temperature {
+                    builder = builder.temperature(temperature);
+                }
+                if let Some(max_tokens) = self.params.max_tokens {
+                    builder = builder.max_tokens(max_tokens);
+                }
+                if let Some(params) = additional_params.clone() {
+                    builder = builder.additional_params(params);
+                }
+                builder.build()
+            })
+            .collect();
+
+        self.fallbacks = self
+            .fallback
+            .iter()
+            .map(|fallback| {
+                let mut action = self.definition();
+                action.label = self.label.clone();
+                action.provider = fallback.provider.clone();
+                action.model = fallback.model.clone();
+                // fallbacks don't have fallbacks of their own
+                action.fallback = Vec::new();
+                action.compile(context).map(|_| action)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(())
+    }
+
+    fn account_to(&mut self, label: &str) {
+        if let Some(account) = self.account.as_mut() {
+            account.action = label.to_string();
+        }
+        for fallback in self.fallbacks.iter_mut() {
+            fallback.account_to(label);
+        }
+    }
+
+    // rotate across the agents of providers with several api keys
+    pub(super) fn next_agent(&self) -> Option<Agent<CompletionModelHandle<'static>>> {
+        if self.agents.is_empty() {
+            return None;
+        }
+        let idx = self.next_agent.fetch_add(1, Ordering::Relaxed) % self.agents.len();
+        Some(self.agents[idx].clone())
+    }
+
+    // the model of this action followed by its fallbacks, in the order they're asked
+    pub(super) fn candidates(&self) -> anyhow::Result<Vec<Candidate>> {
+        std::iter::once(self)
+            .chain(self.fallbacks.iter())
+            .map(|action| {
+                Ok(Candidate {
+                    agent: action.next_agent().ok_or_else(|| {
+                        let missing = provider::missing_env_vars(&action.provider);
+                        if missing.is_empty() {
+                            anyhow::anyhow!("action not compiled")
+                        } else {
+                            anyhow::anyhow!(
+                                "{} is configured from the environment, set {} there",
+                                action.provider,
+                                missing.join(", ")
+                            )
+                        }
+                    })?,
+                    name: format!("{}/{}", action.provider, action.model),
+                    account: action.account.clone(),
+                    timeout: Duration::from_secs(action.timeout.unwrap_or(retry::DEFAULT_TIMEOUT)),
+                    retries: action.retries.unwrap_or(retry::DEFAULT_RETRIES),
+                })
+            })
+            .collect()
+    }
+
     pub fn button_text(&self) -> String {
-        if self.key.is_none() {
-            self.label.clone()
+        if let Some(key) = self.key.as_ref() {
+            format!("[{}] {}", key, self.label)
         } else {
-            format!("[{}] {}", self.key.as_ref().unwrap(), self.label)
+            self.label.clone()
         }
     }
 
-    pub fn trigger(&self, clipboard_text: &str, action_response_tx: mpsc::Sender<Event>) {
-        if let Some(agent) = &self.agent {
-            let prompt = self.prompt.clone() + "\n\n" + clipboard_text;
-            let agent = agent.clone();
-            let do_paste = self.paste;
+    // build the future that runs this action on the given input, it's up to the
+    // executor to drive it and cancel it
+    pub fn task(
+        &self,
+        job_id: JobId,
+        input: &Input,
+        events_tx: mpsc::Sender<Event>,
+    ) -> anyhow::Result<BoxFuture<'static, ()>> {
+        if self.is_pipeline() {
+            if self.stages.is_empty() {
+                return Err(anyhow::anyhow!("action not compiled"));
+            }
+            // every stage is accounted to the same ledger
+            if let Some(account) = self.stages.iter().find_map(|stage| stage.account.as_ref()) {
+                self.check_budget(account, job_id, &events_tx)?;
+            }
+            return Ok(run_pipeline(
+                self.stages.clone(),
+                input.clone(),
+                job_id,
+                self.paste,
+                events_tx,
+            )
+            .boxed());
+        }
 
-            std::thread::spawn(move || {
-                // ugly hack to call async code from a sync context
-                let runtime = tokio::runtime::Builder::new_multi_thread()
-                    .enable_all()
-                    .build();
+        // builtins are done by the time the job starts, there's nothing left to run
+        if let Some(result) = self.run_builtin(input) {
+            let _ = events_tx.send(match result {
+                Ok(response) => Event::Response(job_id, response, self.paste),
+                Err(e) => Event::Error(job_id, e),
+            });
+            return Ok(futures::future::ready(()).boxed());
+        }
 
-                if let Err(e) = runtime {
-                    action_response_tx
-                        .send(Event::Error(anyhow::anyhow!("{}", e)))
-                        .unwrap();
-                    return;
-                }
+        if let Some(invocation) = self.invocation(input)? {
+            return Ok(command_response(invocation, job_id, self.paste, events_tx).boxed());
+        }
 
-                match runtime
-                    .unwrap()
-                    .block_on(async { agent.prompt(prompt).await })
-                {
-                    Ok(response) => action_response_tx
-                        .send(Event::Response(response, do_paste))
-                        .unwrap(),
-                    Err(e) => action_response_tx
-                        .send(Event::Error(anyhow::anyhow!("{}", e)))
-                        .unwrap(),
+        let candidates = self.candidates()?;
+        let prompt = self.build_message(input)?;
+        let slot = match self.response_cache.as_ref() {
+            Some(cache) => Some(cache.slot(&self.cache_scope, &prompt, input.refresh)?),
+            None => None,
+        };
+        // cached responses cost nothing, they're sent right away even past the budget
+        if let Some(response) = slot.as_ref().and_then(|slot| slot.get()) {
+            let _ = events_tx.send(Event::Cached(job_id));
+            let _ = events_tx.send(Event::Response(job_id, response, self.paste));
+            return Ok(futures::future::ready(()).boxed());
+        }
+
+        if let Some(account) = self.account.as_ref() {
+            self.check_budget(account, job_id, &events_tx)?;
+        }
+
+        Ok(stream_response(candidates, prompt, job_id, self.paste, slot, events_tx).boxed())
+    }
+
+    // fails once a hard budget is exceeded, soft ones only warn
+    fn check_budget(
+        &self,
+        account: &Account,
+        job_id: JobId,
+        events_tx: &mpsc::Sender<Event>,
+    ) -> anyhow::Result<()> {
+        let warnings = account
+            .ledger
+            .check_budget()
+            .map_err(|e| anyhow::anyhow!("{} blocked: {}", self.label, e))?;
+        for warning in warnings {
+            let _ = events_tx.send(Event::Warning(job_id, warning));
+        }
+        Ok(())
+    }
+}
+
+// a model to ask, with what it takes to run it on its own
+pub(super) struct Candidate {
+    agent: Agent<CompletionModelHandle<'static>>,
+    // provider/model, shown to tell which model answered
+    name: String,
+    account: Option<Account>,
+    timeout: Duration,
+    retries: u32,
+}
+
+// stream the response sending its chunks as they come, returns the whole text or none
+// if the receiver is gone
+async fn stream_text(
+    candidate: &Candidate,
+    prompt: Message,
+    job_id: JobId,
+    events_tx: &mpsc::Sender<Event>,
+) -> Result<Option<String>, Failure> {
+    let meter = Meter::default();
+    let started_at = Instant::now();
+    let streamed = meter.scope(async {
+        let mut stream = candidate.agent.stream_prompt(prompt.clone()).await;
+        let mut response = String::new();
+
+        loop {
+            // a provider that stops sending anything would keep the job running forever
+            let item = tokio::time::timeout(candidate.timeout, stream.next())
+                .await
+                .map_err(|_| Failure::timeout(candidate.timeout))?;
+            match item {
+                Some(Ok(MultiTurnStreamItem::Text(text))) => {
+                    response.push_str(&text.text);
+                    // the receiver is gone if the UI was closed, just stop streaming
+                    if events_tx.send(Event::Chunk(job_id, text.text)).is_err() {
+                        return Ok(None);
+                    }
                 }
-            });
-        } else {
-            action_response_tx
-                .send(Event::Error(anyhow::anyhow!("action not compiled")))
-                .unwrap();
+                Some(Ok(MultiTurnStreamItem::FinalResponse(_))) => {}
+                Some(Err(e)) => return Err(Failure::from_error(&anyhow::Error::new(e))),
+                None => return Ok(Some(response)),
+            }
+        }
+    });
+    let response = streamed.await?;
+
+    if let (Some(account), Some(response)) = (candidate.account.as_ref(), response.as_ref()) {
+        account.record(meter.usage(), started_at.elapsed(), &prompt, response);
+    }
+    Ok(response)
+}
+
+// ask the candidates in order until one answers, each one is retried with a growing
+// delay on errors that may go away. returns the response along with the index of the
+// candidate that gave it, none if the receiver is gone
+pub(super) async fn complete(
+    candidates: &[Candidate],
+    prompt: Message,
+    job_id: JobId,
+    events_tx: &mpsc::Sender<Event>,
+) -> anyhow::Result<Option<(String, usize)>> {
+    let mut failures = Vec::new();
+    for (idx, candidate) in candidates.iter().enumerate() {
+        let mut attempt = 0;
+        let failure = loop {
+            if events_tx
+                .send(Event::Attempt(job_id, candidate.name.clone()))
+                .is_err()
+            {
+                return Ok(None);
+            }
+            let failure = match stream_text(candidate, prompt.clone(), job_id, events_tx).await {
+                Ok(response) => return Ok(response.map(|response| (response, idx))),
+                Err(failure) => failure,
+            };
+            if !failure.retryable || attempt >= candidate.retries {
+                break failure;
+            }
+
+            let delay = retry::backoff(attempt);
+            eprintln!(
+                "{}: {}, retrying in {}s",
+                candidate.name,
+                failure.message,
+                delay.as_secs()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
+
+        let mut message = format!("{}: {}", candidate.name, failure.message);
+        if attempt > 0 {
+            message.push_str(&format!(" (after {} attempts)", attempt + 1));
+        }
+        if idx + 1 < candidates.len() {
+            eprintln!("{}, trying the next model", message);
+        }
+        failures.push(message);
+    }
+
+    Err(match failures.as_slice() {
+        [failure] => anyhow::anyhow!("{}", failure),
+        failures => anyhow::anyhow!("every model failed:\n{}", failures.join("\n")),
+    })
+}
+
+async fn stream_response(
+    candidates: Vec<Candidate>,
+    prompt: Message,
+    job_id: JobId,
+    do_paste: bool,
+    slot: Option<Slot>,
+    events_tx: mpsc::Sender<Event>,
+) {
+    match complete(&candidates, prompt, job_id, &events_tx).await {
+        Ok(Some((response, idx))) => {
+            // the fallbacks answer for the action's model, not as it
+            if idx == 0
+                && let Some(slot) = slot.as_ref()
+                && let Err(e) = slot.put(&response)
+            {
+                eprintln!("could not cache the response: {}", e);
+            }
+            let _ = events_tx.send(Event::Response(job_id, response, do_paste));
+        }
+        Ok(None) => {}
+        Err(e) => {
+            let _ = events_tx.send(Event::Error(job_id, e));
         }
     }
 }
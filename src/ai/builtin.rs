@@ -0,0 +1,363 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use base64::Engine;
+use heck::{ToKebabCase, ToLowerCamelCase, ToSnakeCase, ToTitleCase, ToUpperCamelCase};
+use md5::Md5;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use regex::Regex;
+use serde::Deserialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+// everything but the unreserved characters of rfc 3986
+const URL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+// query parameters added by ad networks, newsletters and social media to track clicks
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid",
+    "gclid",
+    "gclsrc",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "yclid",
+    "twclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_hsenc",
+    "_hsmi",
+    "mkt_tok",
+    "ref_src",
+    "oly_anon_id",
+    "oly_enc_id",
+    "vero_id",
+    "si",
+];
+
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"https?://[^\s<>"']+"#).unwrap());
+
+// transforms that run locally, without any model or external tool
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Builtin {
+    Uppercase,
+    Lowercase,
+    TitleCase,
+    SnakeCase,
+    CamelCase,
+    PascalCase,
+    KebabCase,
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    HtmlEncode,
+    HtmlDecode,
+    JsonPretty,
+    JsonMinify,
+    YamlPretty,
+    JsonToYaml,
+    YamlToJson,
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    JwtDecode,
+    Uuid,
+    StripTracking,
+    RegexReplace,
+}
+
+impl Builtin {
+    // the arguments this transform takes, asked for in a form when the action doesn't
+    // set them
+    pub fn args(&self) -> &'static [&'static str] {
+        match self {
+            Builtin::RegexReplace => &["find", "replace"],
+            _ => &[],
+        }
+    }
+
+    pub fn apply(&self, text: &str, args: &HashMap<String, String>) -> anyhow::Result<String> {
+        Ok(match self {
+            Builtin::Uppercase => text.to_uppercase(),
+            Builtin::Lowercase => text.to_lowercase(),
+            Builtin::TitleCase => by_line(text, |line| line.to_title_case()),
+            Builtin::SnakeCase => by_line(text, |line| line.to_snake_case()),
+            Builtin::CamelCase => by_line(text, |line| line.to_lower_camel_case()),
+            Builtin::PascalCase => by_line(text, |line| line.to_upper_camel_case()),
+            Builtin::KebabCase => by_line(text, |line| line.to_kebab_case()),
+            Builtin::Base64Encode => base64::engine::general_purpose::STANDARD.encode(text),
+            Builtin::Base64Decode => utf8(base64_decode(text)?)?,
+            Builtin::UrlEncode => utf8_percent_encode(text, URL_ENCODE_SET).to_string(),
+            Builtin::UrlDecode => percent_decode_str(text.trim())
+                .decode_utf8()
+                .map_err(|_| anyhow::anyhow!("decoded text is not valid utf-8"))?
+                .to_string(),
+            Builtin::HtmlEncode => html_escape::encode_quoted_attribute(text).to_string(),
+            Builtin::HtmlDecode => html_escape::decode_html_entities(text).to_string(),
+            Builtin::JsonPretty => serde_json::to_string_pretty(&parse_json(text)?)?,
+            Builtin::JsonMinify => serde_json::to_string(&parse_json(text)?)?,
+            Builtin::YamlPretty => serde_yaml::to_string(&parse_yaml(text)?)?,
+            Builtin::JsonToYaml => serde_yaml::to_string(&parse_json(text)?)?,
+            Builtin::YamlToJson => serde_json::to_string_pretty(&parse_yaml(text)?)?,
+            Builtin::Md5 => hex(&Md5::digest(text)),
+            Builtin::Sha1 => hex(&Sha1::digest(text)),
+            Builtin::Sha256 => hex(&Sha256::digest(text)),
+            Builtin::Sha512 => hex(&Sha512::digest(text)),
+            Builtin::JwtDecode => jwt_decode(text)?,
+            Builtin::Uuid => uuid::Uuid::new_v4().to_string(),
+            Builtin::StripTracking => URL
+                .replace_all(text, |caps: &regex::Captures| strip_tracking(&caps[0]))
+                .to_string(),
+            Builtin::RegexReplace => {
+                let find = args
+                    .get("find")
+                    .ok_or_else(|| anyhow::anyhow!("find is required"))?;
+                let regex = Regex::new(find)?;
+                let replace = args.get("replace").map(String::as_str).unwrap_or_default();
+                regex.replace_all(text, replace).to_string()
+            }
+        })
+    }
+}
+
+// multi line text is converted line by line, so lists of names keep their shape
+fn by_line(text: &str, convert: impl Fn(&str) -> String) -> String {
+    text.lines().map(convert).collect::<Vec<_>>().join("\n")
+}
+
+fn utf8(data: Vec<u8>) -> anyhow::Result<String> {
+    String::from_utf8(data).map_err(|_| anyhow::anyhow!("decoded data is not text"))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// standard or url safe alphabet, with or without padding
+fn base64_decode(text: &str) -> anyhow::Result<Vec<u8>> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let text = text.trim_end_matches('=');
+    let engine = if text.contains(['-', '_']) {
+        &base64::engine::general_purpose::URL_SAFE_NO_PAD
+    } else {
+        &base64::engine::general_purpose::STANDARD_NO_PAD
+    };
+    engine
+        .decode(text)
+        .map_err(|e| anyhow::anyhow!("invalid base64: {}", e))
+}
+
+fn parse_json(text: &str) -> anyhow::Result<serde_json::Value> {
+    serde_json::from_str(text.trim()).map_err(|e| anyhow::anyhow!("invalid json: {}", e))
+}
+
+fn parse_yaml(text: &str) -> anyhow::Result<serde_yaml::Value> {
+    serde_yaml::from_str(text).map_err(|e| anyhow::anyhow!("invalid yaml: {}", e))
+}
+
+// header and payload of a token, the signature is not verified
+fn jwt_decode(text: &str) -> anyhow::Result<String> {
+    let token = text.trim();
+    let token = token.strip_prefix("Bearer ").unwrap_or(token);
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload)) = (parts.next(), parts.next()) else {
+        return Err(anyhow::anyhow!("not a jwt"));
+    };
+
+    let decode = |part: &str, what: &str| -> anyhow::Result<serde_json::Value> {
+        let data =
+            base64_decode(part).map_err(|e| anyhow::anyhow!("invalid jwt {}: {}", what, e))?;
+        serde_json::from_slice(&data).map_err(|e| anyhow::anyhow!("invalid jwt {}: {}", what, e))
+    };
+
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "header": decode(header, "header")?,
+        "payload": decode(payload, "payload")?,
+    }))?)
+}
+
+// drop the tracking parameters from the query of the url, leaving the rest untouched
+fn strip_tracking(url: &str) -> String {
+    let (without_fragment, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+    let Some((base, query)) = without_fragment.split_once('?') else {
+        return url.to_string();
+    };
+
+    let query: Vec<&str> = query
+        .split('&')
+        .filter(|param| {
+            let name = param.split('=').next().unwrap_or_default();
+            !param.is_empty() && !name.starts_with("utm_") && !TRACKING_PARAMS.contains(&name)
+        })
+        .collect();
+
+    let mut stripped = base.to_string();
+    if !query.is_empty() {
+        stripped.push('?');
+        stripped.push_str(&query.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        stripped.push('#');
+        stripped.push_str(fragment);
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(builtin: Builtin, text: &str) -> anyhow::Result<String> {
+        builtin.apply(text, &HashMap::new())
+    }
+
+    #[test]
+    fn converts_case() {
+        for (builtin, text, expected) in [
+            (Builtin::Uppercase, "héllo world", "HÉLLO WORLD"),
+            (Builtin::Lowercase, "HÉLLO World", "héllo world"),
+            (Builtin::TitleCase, "hello world", "Hello World"),
+            (Builtin::SnakeCase, "hello world", "hello_world"),
+            (
+                Builtin::SnakeCase,
+                "helloWorld\nHTTPServer",
+                "hello_world\nhttp_server",
+            ),
+            (Builtin::CamelCase, "hello world", "helloWorld"),
+            (Builtin::PascalCase, "hello_world", "HelloWorld"),
+            (
+                Builtin::KebabCase,
+                "Hello World\nfoo_bar",
+                "hello-world\nfoo-bar",
+            ),
+        ] {
+            assert_eq!(apply(builtin, text).unwrap(), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn decodes_base64() {
+        for (text, expected) in [
+            ("aGVsbG8=", "hello"),
+            // padding and line breaks are optional
+            ("aGVsbG8", "hello"),
+            ("aGVs\nbG8=", "hello"),
+            ("Pz8+", "??>"),
+            // url safe alphabet
+            ("Pz8-", "??>"),
+            ("PDw_", "<<?"),
+        ] {
+            assert_eq!(
+                apply(Builtin::Base64Decode, text).unwrap(),
+                expected,
+                "{:?}",
+                text
+            );
+        }
+        for (text, error) in [
+            ("not base64!", "invalid base64"),
+            ("/w==", "decoded data is not text"),
+        ] {
+            let e = apply(Builtin::Base64Decode, text).unwrap_err();
+            assert!(e.to_string().starts_with(error), "{:?}: {}", text, e);
+        }
+        assert_eq!(apply(Builtin::Base64Encode, "??>").unwrap(), "Pz8+");
+    }
+
+    #[test]
+    fn encodes_and_decodes_urls() {
+        for (builtin, text, expected) in [
+            (Builtin::UrlEncode, "a b/c~é", "a%20b%2Fc~%C3%A9"),
+            (Builtin::UrlDecode, "a%20b%2Fc~%C3%A9", "a b/c~é"),
+            (Builtin::UrlDecode, " %E2%9C%93\n", "✓"),
+        ] {
+            assert_eq!(apply(builtin, text).unwrap(), expected, "{:?}", text);
+        }
+        assert!(apply(Builtin::UrlDecode, "%FF").is_err());
+    }
+
+    #[test]
+    fn decodes_jwt() {
+        let token = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+            eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ.\
+            SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c";
+        for text in [token.to_string(), format!("Bearer {}\n", token)] {
+            let decoded: serde_json::Value =
+                serde_json::from_str(&apply(Builtin::JwtDecode, &text).unwrap()).unwrap();
+            assert_eq!(decoded["header"]["alg"], "HS256");
+            assert_eq!(decoded["payload"]["name"], "John Doe");
+            assert_eq!(decoded["payload"]["iat"], 1516239022);
+        }
+        for (text, error) in [
+            ("abc", "not a jwt"),
+            ("abc.def", "invalid jwt header"),
+            ("eyJhbGciOiJIUzI1NiJ9.bm90IGpzb24", "invalid jwt payload"),
+        ] {
+            let e = apply(Builtin::JwtDecode, text).unwrap_err();
+            assert!(e.to_string().starts_with(error), "{:?}: {}", text, e);
+        }
+    }
+
+    #[test]
+    fn replaces_regex() {
+        let args = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        for (pairs, text, expected) in [
+            (
+                &[("find", r"\s+"), ("replace", " ")][..],
+                "a  b\n\tc",
+                "a b c",
+            ),
+            (
+                &[("find", r"(\w+)@(\w+)"), ("replace", "$2 at $1")][..],
+                "me@home",
+                "home at me",
+            ),
+            // no replacement removes the matches
+            (&[("find", "[0-9]")][..], "a1b22c", "abc"),
+        ] {
+            assert_eq!(
+                Builtin::RegexReplace.apply(text, &args(pairs)).unwrap(),
+                expected
+            );
+        }
+        let e = Builtin::RegexReplace
+            .apply("text", &args(&[("replace", "x")]))
+            .unwrap_err();
+        assert_eq!(e.to_string(), "find is required");
+        assert!(
+            Builtin::RegexReplace
+                .apply("text", &args(&[("find", "(")]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn strips_tracking_params() {
+        for (text, expected) in [
+            (
+                "see https://example.com/a?utm_source=x&id=3&fbclid=y#top now",
+                "see https://example.com/a?id=3#top now",
+            ),
+            ("https://example.com/?gclid=1", "https://example.com/"),
+            ("https://example.com/?q=1", "https://example.com/?q=1"),
+        ] {
+            assert_eq!(apply(Builtin::StripTracking, text).unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{path::PathBuf, time::Duration};
+
+use rig::message::Message;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct Config {
+    // cached responses older than this many seconds are requested again, 0 keeps them
+    // until they're pushed out by newer ones
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+    // the oldest responses are dropped when the cache grows past this many bytes, 0
+    // disables the cache
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+}
+
+fn default_ttl() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_max_size() -> u64 {
+    16 * 1024 * 1024
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ttl: default_ttl(),
+            max_size: default_max_size(),
+        }
+    }
+}
+
+// responses of the actions with cache enabled, one file per request
+#[derive(Clone)]
+pub(crate) struct Cache {
+    dir: PathBuf,
+    config: Config,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf, config: Config) -> Self {
+        Self { dir, config }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.max_size > 0
+    }
+
+    // where the response to the given prompt goes, scope is everything else that
+    // changes the response like provider, model, preamble and parameters
+    pub fn slot(&self, scope: &Value, prompt: &Message, refresh: bool) -> anyhow::Result<Slot> {
+        let request = serde_json::to_vec(&json!({ "scope": scope, "prompt": prompt }))?;
+        let key: String = Sha256::digest(&request)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Ok(Slot {
+            cache: self.clone(),
+            path: self.dir.join(format!("{}.txt", key)),
+            refresh,
+        })
+    }
+
+    // drop the expired responses and the oldest ones past the size limit
+    fn prune(&self) -> anyhow::Result<()> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            entries.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+        // newest first
+        entries.sort_by_key(|(modified, _, _)| std::cmp::Reverse(*modified));
+
+        let mut size = 0;
+        for (modified, len, path) in entries {
+            let expired = self.config.ttl > 0
+                && modified.elapsed().unwrap_or_default() > Duration::from_secs(self.config.ttl);
+            if expired || size + len > self.config.max_size {
+                std::fs::remove_file(path)?;
+            } else {
+                size += len;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the cache entry of a request
+pub(crate) struct Slot {
+    cache: Cache,
+    path: PathBuf,
+    // skip the cached response, a new one replaces it
+    refresh: bool,
+}
+
+impl Slot {
+    pub fn get(&self) -> Option<String> {
+        if self.refresh {
+            return None;
+        }
+
+        let modified = std::fs::metadata(&self.path).ok()?.modified().ok()?;
+        if self.cache.config.ttl > 0
+            && modified.elapsed().unwrap_or_default() > Duration::from_secs(self.cache.config.ttl)
+        {
+            let _ = std::fs::remove_file(&self.path);
+            return None;
+        }
+
+        std::fs::read_to_string(&self.path).ok()
+    }
+
+    pub fn put(&self, response: &str) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.cache.dir)?;
+        std::fs::write(&self.path, response)?;
+        self.cache.prune()
+    }
+}
@@ -0,0 +1,776 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use eframe::egui;
+use global_hotkey::hotkey::HotKey;
+
+use super::{Config, provider, template};
+
+// what a problem refers to, used to find it in the config file
+pub(crate) enum Field {
+    // a top level key
+    Top(&'static str),
+    // an entry of a top level map, like providers or vars
+    Entry(&'static str, String),
+    // a field of the action at the given index
+    Action(usize, &'static str),
+}
+
+pub(crate) struct Problem {
+    pub field: Field,
+    pub message: String,
+    pub warning: bool,
+}
+
+impl Problem {
+    fn error(field: Field, message: String) -> Self {
+        Self {
+            field,
+            message,
+            warning: false,
+        }
+    }
+
+    fn warning(field: Field, message: String) -> Self {
+        Self {
+            field,
+            message,
+            warning: true,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+fn validate_usage(config: &Config, problems: &mut Vec<Problem>) {
+    for (model, price) in config.usage.pricing.iter() {
+        if price.input < 0.0 || price.output < 0.0 {
+            problems.push(Problem::error(
+                Field::Top("usage"),
+                format!("usage: price of {} can't be negative", model),
+            ));
+        }
+    }
+
+    let budget = &config.usage.budget;
+    for (name, limits) in [("daily", budget.daily), ("monthly", budget.monthly)] {
+        if limits.soft.is_some_and(|soft| soft < 0.0) || limits.hard.is_some_and(|hard| hard < 0.0)
+        {
+            problems.push(Problem::error(
+                Field::Top("usage"),
+                format!("usage: {} budget can't be negative", name),
+            ));
+        }
+        if let (Some(soft), Some(hard)) = (limits.soft, limits.hard)
+            && soft >= hard
+        {
+            problems.push(Problem::warning(
+                Field::Top("usage"),
+                format!(
+                    "usage: {} soft budget is not below the hard one, it will never warn",
+                    name
+                ),
+            ));
+        }
+    }
+
+    if budget.is_set() && config.usage.pricing.is_empty() {
+        problems.push(Problem::warning(
+            Field::Top("usage"),
+            "usage: budgets are set but no model has a price, nothing counts toward them"
+                .to_string(),
+        ));
+    }
+}
+
+// checks that don't need to build any client, run before every compile
+pub(crate) fn validate(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    if let Some(theme) = config.theme.as_ref()
+        && !matches!(theme.as_str(), "dark" | "light" | "system")
+    {
+        problems.push(Problem::error(
+            Field::Top("theme"),
+            format!("invalid theme {:?}, must be dark, light or system", theme),
+        ));
+    }
+
+    if let Some(hotkey) = config.hotkey.as_ref()
+        && let Err(e) = HotKey::from_str(hotkey)
+    {
+        problems.push(Problem::error(
+            Field::Top("hotkey"),
+            format!("invalid hotkey {:?}: {}", hotkey, e),
+        ));
+    }
+
+    for name in config.vars.keys() {
+        if template::BUILTIN_VARIABLES.contains(&name.as_str()) {
+            problems.push(Problem::error(
+                Field::Entry("vars", name.clone()),
+                format!("variable {} is reserved and can't be redefined", name),
+            ));
+        }
+    }
+
+    for (name, _) in provider::unused_keys(&config.keys) {
+        problems.push(Problem::warning(
+            Field::Entry("keys", name.clone()),
+            format!(
+                "key {} is not used by any provider, the ones configured from the environment only read it from there",
+                name
+            ),
+        ));
+    }
+
+    validate_usage(config, &mut problems);
+
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut keys: HashMap<egui::Key, usize> = HashMap::new();
+
+    for (idx, action) in config.actions.iter().enumerate() {
+        if let Some(first) = labels.insert(&action.label, idx) {
+            problems.push(Problem::warning(
+                Field::Action(idx, "label"),
+                format!(
+                    "action {}: label already used by action #{}",
+                    action.label,
+                    first + 1
+                ),
+            ));
+        }
+
+        if let Some(key) = action.key.as_ref() {
+            match egui::Key::from_name(key) {
+                None => problems.push(Problem::error(
+                    Field::Action(idx, "key"),
+                    format!("action {}: invalid key {:?}", action.label, key),
+                )),
+                Some(parsed) => {
+                    if let Some(first) = keys.insert(parsed, idx) {
+                        problems.push(Problem::error(
+                            Field::Action(idx, "key"),
+                            format!(
+                                "action {}: key {} already used by {}",
+                                action.label, key, config.actions[first].label
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if action.review && !action.paste {
+            problems.push(Problem::warning(
+                Field::Action(idx, "review"),
+                format!(
+                    "action {}: review has no effect when paste is false",
+                    action.label
+                ),
+            ));
+        }
+
+        if action.cache && (action.is_pipeline() || action.is_command() || action.is_builtin()) {
+            problems.push(Problem::warning(
+                Field::Action(idx, "cache"),
+                format!(
+                    "action {}: cache only applies to actions using a model",
+                    action.label
+                ),
+            ));
+        }
+
+        for condition in action.when.iter() {
+            if let Err(e) = condition.compile() {
+                problems.push(Problem::error(
+                    Field::Action(idx, "when"),
+                    format!("action {}: {}", action.label, e),
+                ));
+            }
+        }
+
+        if action.is_command() || action.is_builtin() {
+            let kinds = [
+                action.is_command(),
+                action.is_builtin(),
+                action.is_pipeline(),
+            ];
+            if kinds.iter().filter(|kind| **kind).count() > 1 {
+                problems.push(Problem::error(
+                    Field::Action(
+                        idx,
+                        if action.is_command() {
+                            "command"
+                        } else {
+                            "builtin"
+                        },
+                    ),
+                    format!(
+                        "action {}: only one of command, builtin and steps can be used",
+                        action.label
+                    ),
+                ));
+            }
+            validate_local(idx, action, &mut problems);
+            continue;
+        }
+
+        if action.is_pipeline() {
+            match action.resolve_stages(&config.actions) {
+                Ok(stages) => {
+                    for stage in stages.iter() {
+                        validate_stage(config, idx, stage, &mut problems);
+                    }
+                }
+                Err(e) => problems.push(Problem::error(
+                    Field::Action(idx, "steps"),
+                    format!("pipeline {}: {}", action.label, e),
+                )),
+            }
+            continue;
+        }
+
+        for (field, value) in [
+            ("prompt", &action.prompt),
+            ("model", &action.model),
+            ("provider", &action.provider),
+        ] {
+            if value.is_empty() {
+                problems.push(Problem::error(
+                    Field::Action(idx, field),
+                    format!("action {}: {} is required", action.label, field),
+                ));
+            }
+        }
+        if !action.provider.is_empty() {
+            validate_stage(config, idx, action, &mut problems);
+        }
+    }
+
+    problems
+}
+
+// checks commands and builtins, the actions that run locally
+fn validate_local(idx: usize, action: &super::Action, problems: &mut Vec<Problem>) {
+    if action.command.as_ref().is_some_and(|c| c.trim().is_empty()) {
+        problems.push(Problem::error(
+            Field::Action(idx, "command"),
+            format!("action {}: command is empty", action.label),
+        ));
+    }
+    if action.timeout == Some(0) {
+        problems.push(Problem::error(
+            Field::Action(idx, "timeout"),
+            format!("action {}: timeout must be at least 1 second", action.label),
+        ));
+    }
+    for (field, value) in [
+        ("prompt", &action.prompt),
+        ("model", &action.model),
+        ("provider", &action.provider),
+    ] {
+        if !value.is_empty() {
+            problems.push(Problem::warning(
+                Field::Action(idx, field),
+                format!(
+                    "action {}: {} is ignored by {} actions",
+                    action.label,
+                    field,
+                    if action.is_command() {
+                        "command"
+                    } else {
+                        "builtin"
+                    }
+                ),
+            ));
+        }
+    }
+
+    if action.retries.is_some() || !action.fallback.is_empty() {
+        problems.push(Problem::warning(
+            Field::Action(
+                idx,
+                if action.retries.is_some() {
+                    "retries"
+                } else {
+                    "fallback"
+                },
+            ),
+            format!(
+                "action {}: retries and fallback only apply to actions using a model",
+                action.label
+            ),
+        ));
+    }
+
+    let Some(builtin) = action.builtin else {
+        return;
+    };
+    for name in action.args.keys() {
+        if !builtin.args().contains(&name.as_str()) {
+            problems.push(Problem::warning(
+                Field::Action(idx, "args"),
+                format!("action {}: unknown argument {}", action.label, name),
+            ));
+        }
+    }
+    if let Some(find) = action.args.get("find")
+        && let Err(e) = regex::Regex::new(find)
+    {
+        problems.push(Problem::error(
+            Field::Action(idx, "args"),
+            format!("action {}: invalid find regex: {}", action.label, e),
+        ));
+    }
+}
+
+// checks the prompt and provider of an action or a pipeline stage, problems are
+// reported on the action at idx
+fn validate_stage(
+    config: &Config,
+    idx: usize,
+    action: &super::Action,
+    problems: &mut Vec<Problem>,
+) {
+    // commands and builtins referenced by a pipeline are checked as actions of their own
+    if action.is_command() || action.is_builtin() {
+        return;
+    }
+    let is_stage = config.actions[idx].is_pipeline();
+    let name = if is_stage {
+        format!("pipeline {} {}", config.actions[idx].label, action.label)
+    } else {
+        format!("action {}", action.label)
+    };
+
+    if let Err(e) = template::Template::parse(&action.prompt)
+        .and_then(|template| template.validate(&config.vars))
+    {
+        problems.push(Problem::error(
+            Field::Action(idx, if is_stage { "steps" } else { "prompt" }),
+            format!("{}: {}", name, e),
+        ));
+    }
+
+    if !is_known_provider(config, &action.provider) {
+        problems.push(Problem::error(
+            Field::Action(idx, if is_stage { "steps" } else { "provider" }),
+            format!(
+                "{}: unknown provider {:?}, must be one of the providers entries or a supported provider name",
+                name, action.provider
+            ),
+        ));
+    }
+
+    let providers = std::iter::once((&action.provider, "provider")).chain(
+        action
+            .fallback
+            .iter()
+            .map(|fallback| (&fallback.provider, "fallback")),
+    );
+    for (provider, key) in providers {
+        let missing = provider::missing_env_vars(provider);
+        if !config.providers.contains_key(provider) && !missing.is_empty() {
+            problems.push(Problem::warning(
+                Field::Action(idx, if is_stage { "steps" } else { key }),
+                format!(
+                    "{}: {} is configured from the environment, set {} there",
+                    name,
+                    provider,
+                    missing.join(", ")
+                ),
+            ));
+        }
+    }
+
+    if action.timeout == Some(0) {
+        problems.push(Problem::error(
+            Field::Action(idx, if is_stage { "steps" } else { "timeout" }),
+            format!("{}: timeout must be at least 1 second", name),
+        ));
+    }
+
+    for (position, fallback) in action.fallback.iter().enumerate() {
+        let field = Field::Action(idx, if is_stage { "steps" } else { "fallback" });
+        if fallback.provider.is_empty() || fallback.model.is_empty() {
+            problems.push(Problem::error(
+                field,
+                format!(
+                    "{}: fallback {} needs both a provider and a model",
+                    name,
+                    position + 1
+                ),
+            ));
+        } else if !is_known_provider(config, &fallback.provider) {
+            problems.push(Problem::error(
+                field,
+                format!(
+                    "{}: fallback {} has an unknown provider {:?}",
+                    name,
+                    position + 1,
+                    fallback.provider
+                ),
+            ));
+        }
+    }
+
+    // the generation parameters are sent to the fallbacks as well
+    let backends: Vec<&str> = std::iter::once(&action.provider)
+        .chain(action.fallback.iter().map(|fallback| &fallback.provider))
+        .map(|name| backend_name(config, name))
+        .collect();
+    if action.params.stop.is_some() && backends.contains(&"openai") {
+        problems.push(Problem::warning(
+            Field::Action(idx, if is_stage { "steps" } else { "stop" }),
+            format!(
+                "{}: stop sequences are not supported by the openai responses api and are ignored",
+                name
+            ),
+        ));
+    }
+    // anthropic counts the thinking in max_tokens and rejects budgets that don't fit in it
+    if let Some(effort) = action.params.reasoning_effort
+        && let Some(max_tokens) = action.params.max_tokens
+        && max_tokens <= effort.budget_tokens()
+        && backends.contains(&"anthropic")
+    {
+        problems.push(Problem::error(
+            Field::Action(idx, if is_stage { "steps" } else { "max_tokens" }),
+            format!(
+                "{}: max_tokens must be above {}, the thinking budget of anthropic models with reasoning_effort {}",
+                name,
+                effort.budget_tokens(),
+                effort.as_str()
+            ),
+        ));
+    }
+}
+
+// the backend a provider name resolves to, named as additional_params expects it
+fn backend_name<'a>(config: &'a Config, name: &'a str) -> &'a str {
+    match config.providers.get(name) {
+        Some(provider) => provider.backend.as_str(),
+        None => provider::Backend::from_name(name).map_or(name, |backend| backend.as_str()),
+    }
+}
+
+fn is_known_provider(config: &Config, name: &str) -> bool {
+    config.providers.contains_key(name)
+        || provider::Backend::from_name(name).is_some()
+        || provider::is_env_provider(name)
+}
+
+// finds where things are defined, good enough for the block style yaml of config files
+struct Locator<'a> {
+    lines: Vec<&'a str>,
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+// where the key defined by the line starts, past the list item marker if any
+fn key_indentation(line: &str) -> usize {
+    indentation(line)
+        + if line.trim_start().starts_with("- ") {
+            2
+        } else {
+            0
+        }
+}
+
+fn is_blank(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with('#')
+}
+
+impl<'a> Locator<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            lines: source.lines().collect(),
+        }
+    }
+
+    // index of the line defining the given key between start and end, at the given
+    // indentation so that keys of nested maps and lists are skipped
+    fn find_key(&self, start: usize, end: usize, indent: usize, key: &str) -> Option<usize> {
+        (start..end).find(|&idx| {
+            let line = self.lines[idx];
+            if is_blank(line) || key_indentation(line) != indent {
+                return false;
+            }
+            let trimmed = line.trim_start().trim_start_matches("- ");
+            let trimmed = trimmed.trim_start_matches(['"', '\'']);
+            trimmed
+                .strip_prefix(key)
+                .map(|rest| rest.trim_start_matches(['"', '\'']).starts_with(':'))
+                .unwrap_or(false)
+        })
+    }
+
+    // the lines belonging to the block of the given line, until indentation goes back
+    fn block_end(&self, line: usize) -> usize {
+        let indent = indentation(self.lines[line]);
+        (line + 1..self.lines.len())
+            .find(|&idx| {
+                let text = self.lines[idx];
+                // list items can be at the same indentation as their parent key
+                !is_blank(text)
+                    && (indentation(text) < indent
+                        || (indentation(text) == indent && !text.trim_start().starts_with("- ")))
+            })
+            .unwrap_or(self.lines.len())
+    }
+
+    fn top(&self, key: &str) -> Option<usize> {
+        (0..self.lines.len()).find(|&idx| {
+            let line = self.lines[idx];
+            indentation(line) == 0
+                && line
+                    .strip_prefix(key)
+                    .map(|rest| rest.starts_with(':'))
+                    .unwrap_or(false)
+        })
+    }
+
+    fn action(&self, index: usize) -> Option<(usize, usize)> {
+        let actions = self.top("actions")?;
+        let end = self.block_end(actions);
+        let items: Vec<usize> = (actions + 1..end)
+            .filter(|&idx| self.lines[idx].trim_start().starts_with("- "))
+            .collect();
+        // nested lists inside actions are indented deeper than the items themselves
+        let indent = items
+            .iter()
+            .map(|&idx| indentation(self.lines[idx]))
+            .min()?;
+        let items: Vec<usize> = items
+            .into_iter()
+            .filter(|&idx| indentation(self.lines[idx]) == indent)
+            .collect();
+        let start = *items.get(index)?;
+        let end = items.get(index + 1).copied().unwrap_or(end);
+        Some((start, end))
+    }
+
+    // 1-based line and column of the field, or of the closest thing found when it's
+    // written in flow style
+    fn locate(&self, field: &Field) -> Option<(usize, usize)> {
+        let line = match field {
+            Field::Top(key) => self.top(key)?,
+            Field::Entry(key, name) => {
+                let top = self.top(key)?;
+                let end = self.block_end(top);
+                (top + 1..end)
+                    .find(|&idx| !is_blank(self.lines[idx]))
+                    .and_then(|first| {
+                        self.find_key(first, end, key_indentation(self.lines[first]), name)
+                    })
+                    .unwrap_or(top)
+            }
+            Field::Action(index, key) => match self.action(*index) {
+                Some((start, end)) => self
+                    .find_key(start, end, key_indentation(self.lines[start]), key)
+                    .unwrap_or(start),
+                None => self.top("actions")?,
+            },
+        };
+        Some((line + 1, key_indentation(self.lines[line]) + 1))
+    }
+}
+
+// validate the configuration file printing every problem found, returns true if there
+// are no errors
+pub(crate) fn check_file(path: &str) -> anyhow::Result<bool> {
+    let path = shellexpand::full(path)?.to_string();
+    let source = std::fs::read_to_string(&path)?;
+
+    let mut config = match serde_yaml::from_str::<Config>(&source) {
+        Ok(config) => config,
+        Err(e) => {
+            let (line, column) = e
+                .location()
+                .map(|location| (location.line(), location.column()))
+                .unwrap_or((1, 1));
+            println!("{}:{}:{}: error: {}", path, line, column, e);
+            return Ok(false);
+        }
+    };
+
+    let mut problems = validate(&config);
+
+    // build the http clients and resolve the credentials, implicit providers are
+    // reported on the first action using them
+    let explicit: Vec<String> = config.providers.keys().cloned().collect();
+    // pipeline errors are already reported by validate
+    let pipelines = config.resolve_pipelines().unwrap_or_default();
+    config.add_implicit_providers(&pipelines);
+    for (name, provider) in config.providers.iter_mut() {
+        if let Err(e) = provider.compile(name, &config.keys) {
+            let field = if explicit.contains(name) {
+                Field::Entry("providers", name.clone())
+            } else {
+                let idx = config
+                    .actions
+                    .iter()
+                    .position(|action| &action.provider == name)
+                    .unwrap_or_default();
+                Field::Action(idx, "provider")
+            };
+            problems.push(Problem::error(field, e.to_string()));
+        }
+    }
+
+    let locator = Locator::new(&source);
+    let mut located: Vec<(usize, usize, Problem)> = problems
+        .into_iter()
+        .map(|problem| {
+            let (line, column) = locator.locate(&problem.field).unwrap_or((1, 1));
+            (line, column, problem)
+        })
+        .collect();
+    located.sort_by_key(|(line, column, _)| (*line, *column));
+
+    let mut errors = 0;
+    for (line, column, problem) in located.iter() {
+        let severity = if problem.warning {
+            "warning"
+        } else {
+            errors += 1;
+            "error"
+        };
+        println!("{}:{}:{}: {}: {}", path, line, column, severity, problem);
+    }
+
+    if errors == 0 {
+        println!(
+            "{}: ok, {} actions, {} warnings",
+            path,
+            config.actions.len(),
+            located.len()
+        );
+    } else {
+        println!("{}: {} errors", path, errors);
+    }
+
+    Ok(errors == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"keys:
+  OPENAI_API_KEY: "sk-1234"
+  # another one
+  "GROQ_API_KEY":
+    - { command: "pass show groq" }
+vars: { language: "French" }
+providers:
+  gateway:
+    backend: openai-compatible
+    headers:
+      local: "yes"
+  local:
+    backend: openai-compatible
+actions:
+  - label: "Fix"
+    prompt: "Fix:"
+    fallback:
+      - provider: "openai"
+        model: "gpt-4o-mini"
+  - { label: "Flow", prompt: "Flow:", model: "gpt-4o" }
+  - label: "Polish"
+    model: "gpt-4o"
+    steps:
+      - action: "Fix"
+      - prompt: "Translate:"
+        model: "gpt-4o-mini"
+    when: [json, url]
+
+  - label: "Replace"
+    args: { find: "a", replace: "b" }
+    key: "R"
+hotkey: "CMD+CTRL+C"
+"#;
+
+    fn locate(source: &str, field: Field) -> Option<(usize, usize)> {
+        Locator::new(source).locate(&field)
+    }
+
+    #[test]
+    fn locates_top_level_keys_and_entries() {
+        for (field, expected) in [
+            (Field::Top("hotkey"), (32, 1)),
+            (Field::Top("actions"), (14, 1)),
+            (Field::Entry("keys", "OPENAI_API_KEY".to_string()), (2, 3)),
+            (Field::Entry("keys", "GROQ_API_KEY".to_string()), (4, 3)),
+            // not the header of the same name nested in another provider
+            (Field::Entry("providers", "local".to_string()), (12, 3)),
+            // flow style maps point at their key
+            (Field::Entry("vars", "language".to_string()), (6, 1)),
+            // missing entries as well
+            (Field::Entry("keys", "XAI_API_KEY".to_string()), (1, 1)),
+        ] {
+            assert_eq!(locate(SOURCE, field), Some(expected));
+        }
+        assert_eq!(locate(SOURCE, Field::Top("theme")), None);
+    }
+
+    #[test]
+    fn locates_action_fields() {
+        for (index, key, expected) in [
+            (0, "label", (15, 5)),
+            (0, "prompt", (16, 5)),
+            (0, "fallback", (17, 5)),
+            // the model of a fallback is not the action's, which has none
+            (0, "model", (15, 5)),
+            (2, "model", (22, 5)),
+            // nor is the prompt of a step
+            (2, "prompt", (21, 5)),
+            (2, "when", (27, 5)),
+            // past the blank line, and not inside the flow style args
+            (3, "key", (31, 5)),
+            (3, "find", (29, 5)),
+            // flow style actions point at the action
+            (1, "model", (20, 5)),
+            (1, "label", (20, 5)),
+            // and actions that don't exist at the list
+            (4, "label", (14, 1)),
+        ] {
+            assert_eq!(
+                locate(SOURCE, Field::Action(index, key)),
+                Some(expected),
+                "actions[{}].{}",
+                index,
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn locates_unindented_lists() {
+        let source = r#"actions:
+- label: "A"
+  model: "m"
+- label: "B"
+  prompt: "p"
+theme: dark
+"#;
+        assert_eq!(locate(source, Field::Action(1, "prompt")), Some((5, 3)));
+        assert_eq!(locate(source, Field::Action(1, "model")), Some((4, 3)));
+        assert_eq!(locate(source, Field::Action(0, "model")), Some((3, 3)));
+        assert_eq!(locate(source, Field::Top("theme")), Some((6, 1)));
+    }
+
+    #[test]
+    fn locates_flow_style_lists() {
+        let source = r#"actions: [{ label: "A" }, { label: "B" }]
+"#;
+        assert_eq!(locate(source, Field::Action(1, "label")), Some((1, 1)));
+    }
+}
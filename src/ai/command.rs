@@ -0,0 +1,129 @@
+use std::{process::Stdio, sync::mpsc, time::Duration};
+
+use tokio::io::AsyncWriteExt;
+
+use super::action::Event;
+use super::executor::JobId;
+
+// how long a command can run when its action doesn't set a timeout
+pub(super) const DEFAULT_TIMEOUT: u64 = 30;
+
+// environment variables commands always get, anything else must be allowed by the action
+const BASE_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "LC_ALL",
+    "LC_CTYPE",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SYSTEMROOT",
+    "PATHEXT",
+    "USERPROFILE",
+];
+
+#[cfg(target_os = "windows")]
+pub(super) fn shell(command: &str) -> std::process::Command {
+    let mut cmd = std::process::Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(not(target_os = "windows"))]
+pub(super) fn shell(command: &str) -> std::process::Command {
+    let mut cmd = std::process::Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+// a command ready to be run on some text, owns everything so it can be moved into a job
+pub(super) struct Invocation {
+    pub command: String,
+    pub text: String,
+    pub timeout: Duration,
+    // names of the extra environment variables to pass
+    pub env: Vec<String>,
+}
+
+impl Invocation {
+    // pipe the text into the command and return what it wrote to stdout
+    pub async fn run(self) -> anyhow::Result<String> {
+        let mut cmd = tokio::process::Command::from(shell(&self.command));
+        cmd.env_clear();
+        for name in BASE_ENV
+            .iter()
+            .copied()
+            .chain(self.env.iter().map(String::as_str))
+        {
+            if let Some(value) = std::env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // when the job is cancelled or times out
+            .kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("could not run {}: {}", self.command, e))?;
+
+        // written while the output is read, a command filling its stdout before reading
+        // all of its input would block otherwise
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("no stdin for {}", self.command))?;
+        let text = self.text;
+        let write = async move {
+            // commands are free to not read their input
+            let _ = stdin.write_all(text.as_bytes()).await;
+        };
+        let run = async {
+            let (_, output) = tokio::join!(write, child.wait_with_output());
+            output
+        };
+
+        let output = tokio::time::timeout(self.timeout, run)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "{} timed out after {}s",
+                    self.command,
+                    self.timeout.as_secs()
+                )
+            })??;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(if stderr.trim().is_empty() {
+                anyhow::anyhow!("{} failed with {}", self.command, output.status)
+            } else {
+                anyhow::anyhow!(
+                    "{} failed with {}:\n\n{}",
+                    self.command,
+                    output.status,
+                    stderr.trim()
+                )
+            });
+        }
+
+        String::from_utf8(output.stdout)
+            .map_err(|_| anyhow::anyhow!("{} output is not valid utf-8", self.command))
+    }
+}
+
+pub(super) async fn command_response(
+    invocation: Invocation,
+    job_id: JobId,
+    do_paste: bool,
+    events_tx: mpsc::Sender<Event>,
+) {
+    let _ = events_tx.send(match invocation.run().await {
+        Ok(response) => Event::Response(job_id, response, do_paste),
+        Err(e) => Event::Error(job_id, e),
+    });
+}
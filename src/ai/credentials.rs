@@ -0,0 +1,74 @@
+use serde::Deserialize;
+
+use super::command::shell;
+
+// where an api key comes from
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub(crate) enum Credential {
+    // the key itself
+    Literal(String),
+    // an environment variable
+    Env { env: String },
+    // the contents of a file
+    File { file: String },
+    // the output of a command, for password managers like pass or gopass
+    Command { command: String },
+}
+
+impl Credential {
+    pub fn resolve(&self) -> anyhow::Result<String> {
+        match self {
+            Credential::Literal(key) => Ok(key.clone()),
+            Credential::Env { env } => std::env::var(env)
+                .map_err(|_| anyhow::anyhow!("environment variable {} is not defined", env)),
+            Credential::File { file } => {
+                let path = shellexpand::full(file)?.to_string();
+                let key = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("could not read {}: {}", path, e))?;
+                non_empty(key, file)
+            }
+            Credential::Command { command } => {
+                let output = shell(command)
+                    .output()
+                    .map_err(|e| anyhow::anyhow!("could not run {}: {}", command, e))?;
+                if !output.status.success() {
+                    return Err(anyhow::anyhow!(
+                        "{} failed with {}: {}",
+                        command,
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                non_empty(String::from_utf8(output.stdout)?, command)
+            }
+        }
+    }
+}
+
+fn non_empty(key: String, source: &str) -> anyhow::Result<String> {
+    // password managers and editors add a trailing newline
+    let key = key.trim();
+    if key.is_empty() {
+        Err(anyhow::anyhow!("{} returned an empty key", source))
+    } else {
+        Ok(key.to_string())
+    }
+}
+
+// one or more credentials, requests are spread across all of them
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub(crate) enum Credentials {
+    One(Credential),
+    Many(Vec<Credential>),
+}
+
+impl Credentials {
+    pub fn resolve(&self) -> anyhow::Result<Vec<String>> {
+        match self {
+            Credentials::One(credential) => Ok(vec![credential.resolve()?]),
+            Credentials::Many(credentials) => credentials.iter().map(|c| c.resolve()).collect(),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+// languages the code detector knows about, with the patterns that hint at each of them
+const CODE_LANGUAGES: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &[
+            r"\bfn \w+\s*[<(]",
+            r"\blet mut\b",
+            r"\bimpl\b",
+            r"\bpub (fn|struct|enum|mod)\b",
+            r"::\w+",
+            r"\buse \w+(::\w+)+",
+            r"->\s*\w+",
+        ],
+    ),
+    (
+        "python",
+        &[
+            r"(?m)^\s*def \w+\(.*\):\s*$",
+            r"(?m)^\s*(from \w+(\.\w+)* )?import \w+",
+            r"(?m)^\s*class \w+(\(.*\))?:\s*$",
+            r"\bself\.\w+",
+            r"(?m)^\s*(if|elif|for|while) .*:\s*$",
+            r"\bNone\b",
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            r"\b(const|let|var) \w+\s*=",
+            r"\bfunction\s*\w*\s*\(",
+            r"=>\s*[{(]",
+            r"\bconsole\.\w+\(",
+            r"\b(require|import)\(",
+            r"(?m)^\s*export (default |const |function )",
+        ],
+    ),
+    (
+        "go",
+        &[
+            r"(?m)^package \w+",
+            r"\bfunc (\(\w+ \*?\w+\) )?\w+\(",
+            r":=",
+            r"\bfmt\.\w+\(",
+            r"\berr != nil\b",
+        ],
+    ),
+    (
+        "java",
+        &[
+            r"\bpublic (static )?(class|void|final)\b",
+            r"\bSystem\.out\.print",
+            r"\bprivate \w+(<.*>)? \w+;",
+            r"@Override",
+            r"\bnew \w+(<.*>)?\(",
+        ],
+    ),
+    (
+        "c",
+        &[
+            r"(?m)^#include\s*[<]",
+            r"\bint main\s*\(",
+            r"\bprintf\s*\(",
+            r"\b(malloc|free|sizeof)\s*\(",
+            r"\bstd::\w+",
+        ],
+    ),
+    (
+        "shell",
+        &[
+            r"(?m)^#!/(usr/)?bin/(env )?(ba|z)?sh",
+            r"(?m)^\s*(sudo|echo|export|cd|grep|curl|apt|brew) ",
+            r"\$\{?\w+\}?",
+            r"\|\s*(grep|awk|sed|xargs)\b",
+            r"(?m)^\s*fi\s*$",
+        ],
+    ),
+    (
+        "sql",
+        &[
+            r"(?i)\bselect\b.+\bfrom\b",
+            r"(?i)\b(insert into|update \w+ set|delete from)\b",
+            r"(?i)\b(where|group by|order by|inner join|left join)\b",
+            r"(?i)\bcreate (table|index|view)\b",
+        ],
+    ),
+    (
+        "html",
+        &[
+            r"(?i)<!doctype html",
+            r"(?i)</?(html|head|body|div|span|p|a|ul|li|table)\b[^>]*>",
+        ],
+    ),
+];
+
+// how many patterns of a language need to match for the text to be considered code
+const MIN_CODE_SCORE: usize = 2;
+// the share of symbols a text needs to be considered code at all
+const MIN_CODE_SYMBOLS_RATIO: f32 = 0.03;
+
+static CODE_PATTERNS: LazyLock<Vec<(&'static str, Vec<Regex>)>> = LazyLock::new(|| {
+    CODE_LANGUAGES
+        .iter()
+        .map(|(language, patterns)| {
+            (
+                *language,
+                patterns
+                    .iter()
+                    .map(|pattern| Regex::new(pattern).unwrap())
+                    .collect(),
+            )
+        })
+        .collect()
+});
+
+static URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(https?|ftp)://[^\s/$.?#][^\s]*$").unwrap());
+
+static EMAIL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[\w.+-]+@[\w-]+(\.[\w-]+)+$").unwrap());
+
+static STACK_TRACE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"(?m)(^Traceback \(most recent call last\):",
+        r"|^\s+at [\w$.<>]+\(.*(\.java|Native Method|Unknown Source).*\)",
+        r"|^\s+at .+ \(.+:\d+:\d+\)$",
+        r"|panicked at .+:\d+:\d+",
+        r"|^goroutine \d+ \[",
+        r"|^\s+File .+, line \d+)"
+    ))
+    .unwrap()
+});
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+    Url,
+    Email,
+    Json,
+    Code,
+    StackTrace,
+    Csv,
+    Text,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Url => "url",
+            Kind::Email => "email",
+            Kind::Json => "json",
+            Kind::Code => "code",
+            Kind::StackTrace => "stack_trace",
+            Kind::Csv => "csv",
+            Kind::Text => "text",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "url" => Some(Kind::Url),
+            "email" => Some(Kind::Email),
+            "json" => Some(Kind::Json),
+            "code" => Some(Kind::Code),
+            "stack_trace" => Some(Kind::StackTrace),
+            "csv" => Some(Kind::Csv),
+            "text" => Some(Kind::Text),
+            _ => None,
+        }
+    }
+}
+
+// what the clipboard text looks like
+#[derive(Clone)]
+pub(crate) struct Detection {
+    pub kind: Kind,
+    // programming language for code, natural language for text
+    pub language: Option<String>,
+}
+
+impl Detection {
+    pub fn badge(&self) -> String {
+        match self.language.as_ref() {
+            Some(language) => format!("{} · {}", self.kind.as_str(), language),
+            None => self.kind.as_str().to_string(),
+        }
+    }
+}
+
+fn is_json(text: &str) -> bool {
+    (text.starts_with('{') || text.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(text).is_ok()
+}
+
+fn is_csv(text: &str) -> bool {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if lines.len() < 2 {
+        return false;
+    }
+    // every row has the same number of separators
+    [',', ';', '\t'].iter().any(|separator| {
+        let count = lines[0].matches(*separator).count();
+        count > 0
+            && lines
+                .iter()
+                .all(|line| line.matches(*separator).count() == count)
+    })
+}
+
+// prose can hit a few keywords too, code is denser in symbols
+fn has_code_symbols(text: &str) -> bool {
+    let symbols = text
+        .chars()
+        .filter(|c| "{}()[];=<>$#:*&|".contains(*c))
+        .count();
+    symbols as f32 / text.chars().count() as f32 >= MIN_CODE_SYMBOLS_RATIO
+}
+
+fn code_language(text: &str) -> Option<&'static str> {
+    if !has_code_symbols(text) {
+        return None;
+    }
+    CODE_PATTERNS
+        .iter()
+        .map(|(language, patterns)| {
+            let score = patterns
+                .iter()
+                .filter(|pattern| pattern.is_match(text))
+                .count();
+            (*language, score)
+        })
+        .filter(|(_, score)| *score >= MIN_CODE_SCORE)
+        .max_by_key(|(_, score)| *score)
+        .map(|(language, _)| language)
+}
+
+fn text_language(text: &str) -> Option<String> {
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().eng_name().to_lowercase())
+}
+
+// detect what kind of content the text is, none if it's empty
+pub(crate) fn detect(text: &str) -> Option<Detection> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let kind = |kind| {
+        Some(Detection {
+            kind,
+            language: None,
+        })
+    };
+
+    if is_json(text) {
+        return kind(Kind::Json);
+    }
+    if URL.is_match(text) {
+        return kind(Kind::Url);
+    }
+    if EMAIL.is_match(text) {
+        return kind(Kind::Email);
+    }
+    if STACK_TRACE.is_match(text) {
+        return kind(Kind::StackTrace);
+    }
+    if let Some(language) = code_language(text) {
+        return Some(Detection {
+            kind: Kind::Code,
+            language: Some(language.to_string()),
+        });
+    }
+    if is_csv(text) {
+        return kind(Kind::Csv);
+    }
+
+    Some(Detection {
+        kind: Kind::Text,
+        language: text_language(text),
+    })
+}
+
+// a when: entry, either a detector name with an optional language like code:python or
+// text:english, or a regex the text must match
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub(crate) enum Condition {
+    Detector(String),
+    Regex { regex: String },
+}
+
+pub(crate) enum Matcher {
+    Kind(Kind, Option<String>),
+    Regex(Regex),
+}
+
+impl Condition {
+    pub fn compile(&self) -> anyhow::Result<Matcher> {
+        match self {
+            Condition::Detector(name) => {
+                let (name, language) = match name.split_once(':') {
+                    Some((name, language)) => (name, Some(language.trim().to_lowercase())),
+                    None => (name.as_str(), None),
+                };
+                let kind = Kind::from_name(name.trim())
+                    .ok_or_else(|| anyhow::anyhow!("unknown detector {}", name))?;
+
+                if let Some(language) = language.as_ref() {
+                    let known = match kind {
+                        Kind::Code => CODE_LANGUAGES.iter().any(|(name, _)| name == language),
+                        Kind::Text => whatlang::Lang::all()
+                            .iter()
+                            .any(|lang| lang.eng_name().eq_ignore_ascii_case(language)),
+                        _ => {
+                            return Err(anyhow::anyhow!(
+                                "{} doesn't detect a language",
+                                kind.as_str()
+                            ));
+                        }
+                    };
+                    if !known {
+                        return Err(anyhow::anyhow!(
+                            "unknown {} language {}",
+                            kind.as_str(),
+                            language
+                        ));
+                    }
+                }
+
+                Ok(Matcher::Kind(kind, language))
+            }
+            Condition::Regex { regex } => Ok(Matcher::Regex(Regex::new(regex)?)),
+        }
+    }
+}
+
+impl Matcher {
+    pub fn matches(&self, detection: Option<&Detection>, text: &str) -> bool {
+        match self {
+            Matcher::Kind(kind, language) => detection.is_some_and(|detection| {
+                detection.kind == *kind
+                    && (language.is_none() || detection.language.as_ref() == language.as_ref())
+            }),
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
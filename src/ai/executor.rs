@@ -0,0 +1,239 @@
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::oneshot;
+
+use super::action::{Action, Event, Input};
+
+// how many finished jobs to keep around for the jobs list
+const MAX_FINISHED_JOBS: usize = 10;
+
+pub(crate) type JobId = u64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum JobState {
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Running => "running",
+            JobState::Done => "done",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        }
+    }
+}
+
+pub(crate) struct Job {
+    pub id: JobId,
+    pub label: String,
+    pub state: JobState,
+    // what has been streamed so far, or the whole response once done
+    pub output: String,
+    // label and output of the completed steps of a pipeline
+    pub steps: Vec<(String, String)>,
+    // the response was taken from the cache
+    pub cached: bool,
+    // provider/model of the last model asked, the one that answered once done
+    pub model: Option<String>,
+    // times a model was asked, more than one after retries and fallbacks
+    pub attempts: u32,
+    pub started_at: Instant,
+    pub finished_at: Option<Instant>,
+
+    // dropping this aborts the job
+    cancel_tx: Option<oneshot::Sender<()>>,
+}
+
+impl Job {
+    pub fn is_running(&self) -> bool {
+        self.state == JobState::Running
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.finished_at.unwrap_or_else(Instant::now) - self.started_at
+    }
+
+    fn finish(&mut self, state: JobState) {
+        self.state = state;
+        self.finished_at = Some(Instant::now());
+        self.cancel_tx = None;
+    }
+}
+
+// runs actions as jobs on a single long lived runtime, so that HTTP clients and their
+// connection pools are reused and several actions can be in flight at the same time
+pub(crate) struct Executor {
+    runtime: tokio::runtime::Runtime,
+    next_id: JobId,
+    // newest first
+    jobs: Vec<Job>,
+    events_tx: mpsc::Sender<Event>,
+    events_rx: mpsc::Receiver<Event>,
+}
+
+impl Executor {
+    pub fn new() -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("clipbud-executor")
+            .build()?;
+        let (events_tx, events_rx) = mpsc::channel();
+
+        Ok(Self {
+            runtime,
+            next_id: 1,
+            jobs: Vec::new(),
+            events_tx,
+            events_rx,
+        })
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    pub fn job(&self, id: JobId) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn running(&self) -> usize {
+        self.jobs.iter().filter(|job| job.is_running()).count()
+    }
+
+    pub fn submit(&mut self, action: &Action, input: &Input) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+        let job = Job {
+            id,
+            label: action.label.clone(),
+            state: JobState::Running,
+            output: String::new(),
+            steps: Vec::new(),
+            cached: false,
+            model: None,
+            attempts: 0,
+            started_at: Instant::now(),
+            finished_at: None,
+            cancel_tx: Some(cancel_tx),
+        };
+
+        match action.task(id, input, self.events_tx.clone()) {
+            Ok(task) => {
+                println!("starting job #{} ({})", id, action.label);
+                self.runtime.spawn(async move {
+                    tokio::select! {
+                        // cancelled, whatever was in flight is dropped
+                        _ = cancel_rx => {}
+                        _ = task => {}
+                    }
+                });
+            }
+            // reported as any other failure on the next poll
+            Err(e) => {
+                let _ = self.events_tx.send(Event::Error(id, e));
+            }
+        }
+
+        self.jobs.insert(0, job);
+        self.prune();
+
+        id
+    }
+
+    pub fn cancel(&mut self, id: JobId) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id)
+            && job.is_running()
+        {
+            println!("cancelling job #{} ({})", id, job.label);
+            job.finish(JobState::Cancelled);
+        }
+    }
+
+    // drain the events received since the last call and update the jobs accordingly,
+    // returns the events that completed a job and the warnings so the caller can act on them
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut completed = Vec::new();
+
+        while let Ok(event) = self.events_rx.try_recv() {
+            let id = match &event {
+                Event::Chunk(id, _)
+                | Event::Response(id, _, _)
+                | Event::Step(id, _, _)
+                | Event::Cached(id)
+                | Event::Attempt(id, _)
+                | Event::Warning(id, _)
+                | Event::Error(id, _) => *id,
+            };
+            // events from jobs that were cancelled or pruned are dropped
+            let Some(job) = self
+                .jobs
+                .iter_mut()
+                .find(|job| job.id == id && job.is_running())
+            else {
+                continue;
+            };
+
+            match &event {
+                Event::Chunk(_, chunk) => {
+                    job.output.push_str(chunk);
+                    continue;
+                }
+                Event::Step(_, label, output) => {
+                    // the next step streams from scratch, asking its own model
+                    job.steps.push((label.clone(), output.clone()));
+                    job.output.clear();
+                    job.attempts = 0;
+                    continue;
+                }
+                Event::Cached(_) => {
+                    job.cached = true;
+                    continue;
+                }
+                Event::Attempt(_, model) => {
+                    // what a failed attempt streamed is not part of the response
+                    job.output.clear();
+                    job.model = Some(model.clone());
+                    job.attempts += 1;
+                    continue;
+                }
+                // passed on for the caller to show, the job goes on
+                Event::Warning(..) => {}
+                Event::Response(_, response, _) => {
+                    job.output = response.clone();
+                    job.finish(JobState::Done);
+                }
+                Event::Error(_, error) => {
+                    job.output = error.to_string();
+                    job.finish(JobState::Failed);
+                }
+            }
+
+            completed.push(event);
+        }
+
+        self.prune();
+
+        completed
+    }
+
+    fn prune(&mut self) {
+        let mut finished = 0;
+        self.jobs.retain(|job| {
+            if job.is_running() {
+                return true;
+            }
+            finished += 1;
+            finished <= MAX_FINISHED_JOBS
+        });
+    }
+}
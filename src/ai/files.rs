@@ -0,0 +1,90 @@
+use std::{io::Read, path::Path};
+
+use serde::Deserialize;
+
+// how many bytes are inspected to tell binary files apart from text files
+const BINARY_SNIFF_SIZE: usize = 8192;
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct Config {
+    // files bigger than this are skipped
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    // at most this many files are read, the rest are skipped
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+fn default_max_file_size() -> u64 {
+    256 * 1024
+}
+
+fn default_max_files() -> usize {
+    20
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_file_size: default_max_file_size(),
+            max_files: default_max_files(),
+        }
+    }
+}
+
+// read the given text files into a single block that can be appended to a prompt,
+// files that can't be used are listed with the reason they were skipped
+pub(crate) fn read_files(paths: &[String], config: &Config) -> String {
+    let mut block = String::new();
+
+    for (idx, path) in paths.iter().enumerate() {
+        if !block.is_empty() {
+            block.push_str("\n\n");
+        }
+
+        if idx >= config.max_files {
+            block.push_str(&format!(
+                "--- {} more files skipped (max {}) ---",
+                paths.len() - idx,
+                config.max_files
+            ));
+            break;
+        }
+
+        match read_file(Path::new(path), config) {
+            Ok(contents) => block.push_str(&format!("--- {} ---\n{}", path, contents)),
+            Err(e) => {
+                eprintln!("skipping {}: {}", path, e);
+                block.push_str(&format!("--- {} (skipped: {}) ---", path, e));
+            }
+        }
+    }
+
+    block
+}
+
+fn read_file(path: &Path, config: &Config) -> anyhow::Result<String> {
+    let metadata = std::fs::metadata(path)?;
+    if metadata.is_dir() {
+        return Err(anyhow::anyhow!("directory"));
+    }
+    if metadata.len() > config.max_file_size {
+        return Err(anyhow::anyhow!(
+            "larger than {} bytes",
+            config.max_file_size
+        ));
+    }
+
+    let mut data = Vec::with_capacity(metadata.len() as usize);
+    std::fs::File::open(path)?
+        .take(config.max_file_size)
+        .read_to_end(&mut data)?;
+
+    // NUL bytes don't show up in text files
+    let sniff = &data[..data.len().min(BINARY_SNIFF_SIZE)];
+    if sniff.contains(&0) {
+        return Err(anyhow::anyhow!("binary file"));
+    }
+
+    String::from_utf8(data).map_err(|_| anyhow::anyhow!("not valid UTF-8 text"))
+}
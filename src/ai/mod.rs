@@ -2,10 +2,36 @@ use std::{collections::HashMap, path::PathBuf};
 
 use serde::Deserialize;
 
+use crate::history;
+
 mod action;
+mod builtin;
+mod cache;
+mod check;
+mod command;
+mod credentials;
+mod detect;
+mod executor;
+mod files;
+mod params;
+mod pipeline;
+mod provider;
+mod retry;
+mod template;
+mod usage;
+mod watcher;
 
 pub(crate) use action::Action;
+use action::Context as ActionContext;
 pub(crate) use action::Event as ActionEvent;
+pub(crate) use action::Input as ActionInput;
+pub(crate) use action::InputFormat;
+pub(crate) use check::check_file;
+pub(crate) use detect::{Detection, detect};
+pub(crate) use executor::{Executor, Job, JobId};
+pub(crate) use files::read_files;
+pub(crate) use usage::{Ledger, Summary as UsageSummary, format_cost, format_tokens};
+pub(crate) use watcher::start_watcher;
 
 #[derive(Deserialize)]
 pub(crate) enum ButtonsWrap {
@@ -26,9 +52,37 @@ pub(crate) struct Config {
     #[serde(default = "default_ui_scale")]
     pub ui_scale: Option<f32>,
 
+    // set to false to disable the default system preamble
+    #[serde(default = "default_system_preamble")]
+    pub system_preamble: bool,
+
     pub actions: Vec<Action>,
+    // credentials for the providers, by the name of their environment variable
+    #[serde(default = "HashMap::new")]
+    pub keys: HashMap<String, credentials::Credentials>,
+    // named endpoints that actions can use as their provider
     #[serde(default = "HashMap::new")]
-    pub keys: HashMap<String, String>,
+    pub providers: HashMap<String, provider::Provider>,
+    // user defined variables that can be used in the action prompts
+    #[serde(default = "HashMap::new")]
+    pub vars: HashMap<String, String>,
+
+    #[serde(default)]
+    pub history: history::Config,
+    #[serde(default)]
+    pub files: files::Config,
+    #[serde(default)]
+    pub cache: cache::Config,
+    // prices of the models and spending limits
+    #[serde(default)]
+    pub usage: usage::Config,
+
+    // where this configuration was loaded from
+    #[serde(skip)]
+    pub path: PathBuf,
+    // made when compiling, so the totals it keeps are shared by the actions and the ui
+    #[serde(skip)]
+    ledger: Option<Ledger>,
 }
 
 fn default_buttons_wrap() -> ButtonsWrap {
@@ -39,6 +93,10 @@ fn default_ui_scale() -> Option<f32> {
     Some(1.0)
 }
 
+fn default_system_preamble() -> bool {
+    true
+}
+
 impl Config {
     pub fn default_path() -> PathBuf {
         PathBuf::from(shellexpand::full("~/.clipbud/").unwrap().to_string())
@@ -52,6 +110,38 @@ impl Config {
         Self::default_path().join(".lock")
     }
 
+    pub fn default_history_file() -> PathBuf {
+        Self::default_path().join("history.jsonl")
+    }
+
+    pub fn default_cache_dir() -> PathBuf {
+        Self::default_path().join("cache")
+    }
+
+    pub fn default_usage_file() -> PathBuf {
+        Self::default_path().join("usage.jsonl")
+    }
+
+    // in a directory of its own that only the user can enter
+    pub fn default_socket_file() -> PathBuf {
+        Self::default_path().join("run").join("clipbud.sock")
+    }
+
+    pub fn default_system_file() -> PathBuf {
+        Self::default_path().join("system.md")
+    }
+
+    // the user's system.md if present, the bundled one otherwise
+    fn load_system_preamble() -> anyhow::Result<String> {
+        let user_system = Self::default_system_file();
+        if user_system.exists() {
+            eprintln!("loading system preamble from {}", user_system.display());
+            Ok(std::fs::read_to_string(user_system)?)
+        } else {
+            Ok(include_str!("system.md").to_string())
+        }
+    }
+
     pub fn create_user_data() -> anyhow::Result<()> {
         // create the user folder if needed
         let user_path = Self::default_path();
@@ -74,28 +164,114 @@ impl Config {
         Ok(())
     }
 
+    // actions, pipeline stages and fallbacks using a plain provider name get an implicit endpoint
+    // with default settings
+    fn add_implicit_providers(&mut self, stages: &[(usize, Vec<Action>)]) {
+        let actions = self
+            .actions
+            .iter()
+            .chain(stages.iter().flat_map(|(_, stages)| stages.iter()));
+        let names: Vec<String> = actions
+            .flat_map(|action| {
+                std::iter::once(&action.provider)
+                    .chain(action.fallback.iter().map(|fallback| &fallback.provider))
+            })
+            .cloned()
+            .collect();
+        for name in names {
+            if !self.providers.contains_key(&name)
+                && let Some(backend) = provider::Backend::from_name(&name)
+            {
+                self.providers
+                    .insert(name, provider::Provider::new(backend));
+            }
+        }
+    }
+
+    // steps can refer to other actions, so pipelines are resolved before compiling,
+    // returns the index of each pipeline along with its stages
+    fn resolve_pipelines(&self) -> anyhow::Result<Vec<(usize, Vec<Action>)>> {
+        let mut pipelines = Vec::new();
+        for (idx, action) in self.actions.iter().enumerate() {
+            if action.is_pipeline() {
+                let stages = action
+                    .resolve_stages(&self.actions)
+                    .map_err(|e| anyhow::anyhow!("pipeline {}: {}", action.label, e))?;
+                pipelines.push((idx, stages));
+            }
+        }
+        Ok(pipelines)
+    }
+
     pub fn compile(&mut self) -> anyhow::Result<()> {
-        // set environment variables if defined in the config file
-        for (key, value) in self.keys.iter() {
-            println!("setting environment variable {}", key);
-            unsafe {
-                std::env::set_var(key, value);
+        for problem in check::validate(self) {
+            if problem.warning {
+                eprintln!("warning: {}", problem);
+            } else {
+                return Err(anyhow::anyhow!("{}", problem));
             }
         }
 
+        let pipelines = self.resolve_pipelines()?;
+        self.add_implicit_providers(&pipelines);
+
+        for (name, provider) in self.providers.iter_mut() {
+            provider.compile(name, &self.keys)?;
+        }
+
+        let preamble = if self.system_preamble {
+            Some(Self::load_system_preamble()?)
+        } else {
+            None
+        };
+        let cache = cache::Cache::new(Self::default_cache_dir(), self.cache.clone());
+        let ledger = Ledger::new(Self::default_usage_file(), self.usage.clone());
+        self.ledger = Some(ledger.clone());
+        let context = ActionContext {
+            vars: &self.vars,
+            preamble: preamble.as_deref(),
+            providers: &self.providers,
+            cache: &cache,
+            ledger: &ledger,
+        };
+
         for action in self.actions.iter_mut() {
-            action.compile()?;
+            action.compile(&context)?;
+        }
+        for (idx, stages) in pipelines {
+            self.actions[idx].compile_stages(stages, &context)?;
         }
         Ok(())
     }
 
-    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+    // parse the configuration file without compiling it, so it can be tweaked first
+    pub fn load(path: &str) -> anyhow::Result<Self> {
         let path = shellexpand::full(path)?.to_string();
-        println!("loading config from: {}", path);
-        let config = std::fs::read_to_string(path)?;
+        eprintln!("loading config from: {}", path);
+        let config = std::fs::read_to_string(&path)?;
         let mut config = serde_yaml::from_str::<Self>(&config)?;
+        config.path = PathBuf::from(path);
+
+        Ok(config)
+    }
+
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let mut config = Self::load(path)?;
         config.compile()?;
 
         Ok(config)
     }
+
+    // where the completions of the actions are accounted
+    pub fn ledger(&self) -> Ledger {
+        self.ledger
+            .clone()
+            .unwrap_or_else(|| Ledger::new(Self::default_usage_file(), self.usage.clone()))
+    }
+
+    pub fn action_index(&self, label: &str) -> Option<usize> {
+        self.actions
+            .iter()
+            .position(|action| action.label.eq_ignore_ascii_case(label))
+    }
 }
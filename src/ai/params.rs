@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+#[derive(Deserialize, Clone, Copy)]
+pub(crate) enum ReasoningEffort {
+    #[serde(rename = "minimal")]
+    Minimal,
+    #[serde(rename = "low")]
+    Low,
+    #[serde(rename = "medium")]
+    Medium,
+    #[serde(rename = "high")]
+    High,
+}
+
+impl ReasoningEffort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Minimal => "minimal",
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+
+    // for providers that take a thinking budget in tokens instead of an effort level
+    pub fn budget_tokens(&self) -> u64 {
+        match self {
+            ReasoningEffort::Minimal => 512,
+            ReasoningEffort::Low => 1024,
+            ReasoningEffort::Medium => 8192,
+            ReasoningEffort::High => 24576,
+        }
+    }
+}
+
+// generation parameters, all optional so that provider defaults apply when not set
+#[derive(Deserialize, Clone, Default)]
+pub(crate) struct GenerationParams {
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub top_p: Option<f64>,
+    pub stop: Option<Vec<String>>,
+    pub reasoning_effort: Option<ReasoningEffort>,
+    // passed as is to the provider, merged over the parameters above
+    #[serde(default)]
+    pub provider_options: Map<String, Value>,
+}
+
+impl GenerationParams {
+    // build the provider specific additional_params for the parameters that rig doesn't
+    // have a builder method for, returns none if there's nothing to send
+    pub fn additional_params(&self, provider: &str) -> Option<Value> {
+        let mut params = match provider {
+            "gemini" => self.gemini_params(),
+            "anthropic" => self.anthropic_params(),
+            "openai" => self.openai_params(),
+            // everything else speaks the openai chat completions dialect
+            _ => self.chat_completions_params(),
+        };
+
+        merge(&mut params, Value::Object(self.provider_options.clone()));
+
+        match params.as_object() {
+            Some(map) if map.is_empty() => None,
+            _ => Some(params),
+        }
+    }
+
+    fn gemini_params(&self) -> Value {
+        // gemini returns an error if generationConfig is not passed, temperature and
+        // max tokens are filled in by rig from the request
+        let mut generation_config = json!({
+            "temperature": 1.0,
+            "maxOutputTokens": 4096,
+        });
+        if let Some(top_p) = self.top_p {
+            generation_config["topP"] = json!(top_p);
+        }
+        if let Some(stop) = self.stop.as_ref() {
+            generation_config["stopSequences"] = json!(stop);
+        }
+        if let Some(effort) = self.reasoning_effort {
+            generation_config["thinkingConfig"] =
+                json!({ "thinkingBudget": effort.budget_tokens() });
+        }
+        json!({ "generationConfig": generation_config })
+    }
+
+    fn anthropic_params(&self) -> Value {
+        let mut params = json!({});
+        if let Some(top_p) = self.top_p {
+            params["top_p"] = json!(top_p);
+        }
+        if let Some(stop) = self.stop.as_ref() {
+            params["stop_sequences"] = json!(stop);
+        }
+        if let Some(effort) = self.reasoning_effort {
+            params["thinking"] = json!({
+                "type": "enabled",
+                "budget_tokens": effort.budget_tokens(),
+            });
+        }
+        params
+    }
+
+    fn openai_params(&self) -> Value {
+        // rig uses the responses api for openai, which has no stop sequences
+        let mut params = json!({});
+        if let Some(top_p) = self.top_p {
+            params["top_p"] = json!(top_p);
+        }
+        if let Some(effort) = self.reasoning_effort {
+            params["reasoning"] = json!({ "effort": effort.as_str() });
+        }
+        params
+    }
+
+    fn chat_completions_params(&self) -> Value {
+        let mut params = json!({});
+        if let Some(top_p) = self.top_p {
+            params["top_p"] = json!(top_p);
+        }
+        if let Some(stop) = self.stop.as_ref() {
+            params["stop"] = json!(stop);
+        }
+        if let Some(effort) = self.reasoning_effort {
+            params["reasoning_effort"] = json!(effort.as_str());
+        }
+        params
+    }
+}
+
+// recursively merge objects, anything else in other replaces what's in base
+fn merge(base: &mut Value, other: Value) {
+    match (base, other) {
+        (Value::Object(base), Value::Object(other)) => {
+            for (key, value) in other {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
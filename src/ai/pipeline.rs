@@ -0,0 +1,95 @@
+use std::sync::{Arc, mpsc};
+
+use serde::Deserialize;
+
+use super::action::{Action, Event, Input, complete};
+use super::executor::JobId;
+use super::params::GenerationParams;
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub(crate) enum Step {
+    // run another action, by label
+    Action {
+        action: String,
+    },
+    // an inline prompt, model and provider default to the pipeline's
+    Prompt {
+        prompt: String,
+        model: Option<String>,
+        provider: Option<String>,
+        system: Option<String>,
+        #[serde(flatten)]
+        params: Box<GenerationParams>,
+    },
+}
+
+// run the stages in order, each one getting the previous one's output as its text
+pub(crate) async fn run_pipeline(
+    stages: Vec<Arc<Action>>,
+    mut input: Input,
+    job_id: JobId,
+    do_paste: bool,
+    events_tx: mpsc::Sender<Event>,
+) {
+    for (idx, stage) in stages.iter().enumerate() {
+        let output = match stage.run(&input, job_id, &events_tx).await {
+            Ok(Some(output)) => output,
+            // the receiver is gone, nobody is waiting for the result
+            Ok(None) => return,
+            Err(e) => {
+                let _ = events_tx.send(Event::Error(
+                    job_id,
+                    anyhow::anyhow!("step {} ({}) failed: {}", idx + 1, stage.label, e),
+                ));
+                return;
+            }
+        };
+
+        if idx == stages.len() - 1 {
+            let _ = events_tx.send(Event::Response(job_id, output, do_paste));
+            return;
+        }
+
+        if events_tx
+            .send(Event::Step(job_id, stage.label.clone(), output.clone()))
+            .is_err()
+        {
+            return;
+        }
+        input = Input {
+            text: Some(output),
+            html: None,
+            rtf: None,
+            image: None,
+            files: None,
+            previous_text: input.previous_text.take(),
+            args: std::mem::take(&mut input.args),
+            refresh: input.refresh,
+        };
+    }
+}
+
+impl Action {
+    // run this stage on the given input streaming its chunks if it's a model, returns
+    // none if the receiver is gone
+    async fn run(
+        &self,
+        input: &Input,
+        job_id: JobId,
+        events_tx: &mpsc::Sender<Event>,
+    ) -> anyhow::Result<Option<String>> {
+        if let Some(result) = self.run_builtin(input) {
+            return result.map(Some);
+        }
+        if let Some(invocation) = self.invocation(input)? {
+            return invocation.run().await.map(Some);
+        }
+
+        let candidates = self.candidates()?;
+        let prompt = self.build_message(input)?;
+        Ok(complete(&candidates, prompt, job_id, events_tx)
+            .await?
+            .map(|(response, _)| response))
+    }
+}
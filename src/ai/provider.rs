@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use rig::{
+    agent::AgentBuilder,
+    client::completion::{CompletionClient, CompletionModelHandle},
+    providers::{
+        anthropic, deepseek, gemini, groq, mistral, ollama, openai, openrouter, perplexity,
+        together, xai,
+    },
+};
+use serde::Deserialize;
+
+use super::credentials::Credentials;
+use super::usage::metered;
+
+// rig providers that can only be configured from their environment variables, with the
+// ones they need (any of a group will do), rig panics when they're missing
+const ENV_PROVIDERS: &[(&str, &[&[&str]])] = &[
+    (
+        "azure",
+        &[
+            &["AZURE_API_KEY", "AZURE_TOKEN"],
+            &["AZURE_API_VERSION"],
+            &["AZURE_ENDPOINT"],
+        ],
+    ),
+    ("cohere", &[&["COHERE_API_KEY"]]),
+    ("galadriel", &[&["GALADRIEL_API_KEY"]]),
+    ("huggingface", &[&["HUGGINGFACE_API_KEY"]]),
+    ("hyperbolic", &[&["HYPERBOLIC_API_KEY"]]),
+    ("mira", &[&["MIRA_API_KEY"]]),
+    ("moonshot", &[&["MOONSHOT_API_KEY"]]),
+];
+
+pub(crate) fn is_env_provider(name: &str) -> bool {
+    ENV_PROVIDERS.iter().any(|(provider, _)| *provider == name)
+}
+
+// the environment variables a provider configured from the environment needs but aren't
+// set, empty for the other providers
+pub(crate) fn missing_env_vars(name: &str) -> Vec<String> {
+    let Some((_, groups)) = ENV_PROVIDERS.iter().find(|(provider, _)| *provider == name) else {
+        return Vec::new();
+    };
+    groups
+        .iter()
+        .filter(|group| !group.iter().any(|var| std::env::var_os(var).is_some()))
+        .map(|group| group.join(" or "))
+        .collect()
+}
+
+// every backend, to find the keys none of them read
+const BACKENDS: &[Backend] = &[
+    Backend::OpenAI,
+    Backend::OpenAICompatible,
+    Backend::Anthropic,
+    Backend::Gemini,
+    Backend::Ollama,
+    Backend::OpenRouter,
+    Backend::Groq,
+    Backend::DeepSeek,
+    Backend::Mistral,
+    Backend::Xai,
+    Backend::Together,
+    Backend::Perplexity,
+];
+
+// keys that no backend reads, the providers configured from the environment only see
+// the real environment
+pub(crate) fn unused_keys(
+    keys: &HashMap<String, Credentials>,
+) -> impl Iterator<Item = (&String, &Credentials)> {
+    keys.iter().filter(|(name, _)| {
+        !BACKENDS
+            .iter()
+            .any(|backend| backend.api_key_env() == Some(name.as_str()))
+    })
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub(crate) enum Backend {
+    #[serde(rename = "openai")]
+    OpenAI,
+    // any server speaking the openai chat completions api: gateways, llama.cpp, vllm, ...
+    #[serde(rename = "openai-compatible")]
+    OpenAICompatible,
+    #[serde(rename = "anthropic")]
+    Anthropic,
+    #[serde(rename = "gemini")]
+    Gemini,
+    #[serde(rename = "ollama")]
+    Ollama,
+    #[serde(rename = "openrouter")]
+    OpenRouter,
+    #[serde(rename = "groq")]
+    Groq,
+    #[serde(rename = "deepseek")]
+    DeepSeek,
+    #[serde(rename = "mistral")]
+    Mistral,
+    #[serde(rename = "xai")]
+    Xai,
+    #[serde(rename = "together")]
+    Together,
+    #[serde(rename = "perplexity")]
+    Perplexity,
+}
+
+impl Backend {
+    // for actions that use a plain provider name instead of a named endpoint
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "openai" => Some(Backend::OpenAI),
+            "anthropic" => Some(Backend::Anthropic),
+            "gemini" | "google" => Some(Backend::Gemini),
+            "ollama" => Some(Backend::Ollama),
+            "openrouter" => Some(Backend::OpenRouter),
+            "groq" => Some(Backend::Groq),
+            "deepseek" => Some(Backend::DeepSeek),
+            "mistral" => Some(Backend::Mistral),
+            "xai" => Some(Backend::Xai),
+            "together" => Some(Backend::Together),
+            "perplexity" => Some(Backend::Perplexity),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::OpenAI => "openai",
+            Backend::OpenAICompatible => "openai-compatible",
+            Backend::Anthropic => "anthropic",
+            Backend::Gemini => "gemini",
+            Backend::Ollama => "ollama",
+            Backend::OpenRouter => "openrouter",
+            Backend::Groq => "groq",
+            Backend::DeepSeek => "deepseek",
+            Backend::Mistral => "mistral",
+            Backend::Xai => "xai",
+            Backend::Together => "together",
+            Backend::Perplexity => "perplexity",
+        }
+    }
+
+    // where the api key is read from when the endpoint doesn't set one, compatible servers
+    // are someone else's so they only get the key they are given
+    fn api_key_env(&self) -> Option<&'static str> {
+        match self {
+            Backend::OpenAI => Some("OPENAI_API_KEY"),
+            Backend::OpenAICompatible => None,
+            Backend::Anthropic => Some("ANTHROPIC_API_KEY"),
+            Backend::Gemini => Some("GEMINI_API_KEY"),
+            Backend::Ollama => None,
+            Backend::OpenRouter => Some("OPENROUTER_API_KEY"),
+            Backend::Groq => Some("GROQ_API_KEY"),
+            Backend::DeepSeek => Some("DEEPSEEK_API_KEY"),
+            Backend::Mistral => Some("MISTRAL_API_KEY"),
+            Backend::Xai => Some("XAI_API_KEY"),
+            Backend::Together => Some("TOGETHER_API_KEY"),
+            Backend::Perplexity => Some("PERPLEXITY_API_KEY"),
+        }
+    }
+
+    // local servers usually don't need a key
+    fn requires_api_key(&self) -> bool {
+        !matches!(self, Backend::Ollama | Backend::OpenAICompatible)
+    }
+}
+
+// a named endpoint actions can refer to
+#[derive(Deserialize)]
+pub(crate) struct Provider {
+    pub backend: Backend,
+    pub base_url: Option<String>,
+    // extra headers sent with every request
+    #[serde(default = "HashMap::new")]
+    pub headers: HashMap<String, String>,
+    // http://, https:// or socks5:// proxy url
+    pub proxy: Option<String>,
+    // a single credential or a list of them to spread requests across
+    pub api_key: Option<Credentials>,
+
+    // shared by all the actions using this endpoint
+    #[serde(skip)]
+    http_client: Option<reqwest::Client>,
+    #[serde(skip)]
+    api_keys: Vec<String>,
+}
+
+impl Provider {
+    pub fn new(backend: Backend) -> Self {
+        Self {
+            backend,
+            base_url: None,
+            headers: HashMap::new(),
+            proxy: None,
+            api_key: None,
+            http_client: None,
+            api_keys: Vec::new(),
+        }
+    }
+
+    // keys maps environment variable names to credentials, used when the endpoint
+    // doesn't set its own api_key
+    pub fn compile(
+        &mut self,
+        name: &str,
+        keys: &HashMap<String, Credentials>,
+    ) -> anyhow::Result<()> {
+        let http_client = self
+            .build_http_client()
+            .map_err(|e| anyhow::anyhow!("provider {}: {}", name, e))?;
+        self.http_client = Some(http_client);
+        self.api_keys = self
+            .resolve_api_keys(keys)
+            .map_err(|e| anyhow::anyhow!("provider {}: {}", name, e))?;
+        Ok(())
+    }
+
+    fn build_http_client(&self) -> anyhow::Result<reqwest::Client> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in self.headers.iter() {
+            headers.insert(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes())?,
+                reqwest::header::HeaderValue::from_str(value)?,
+            );
+        }
+
+        let mut builder = reqwest::Client::builder().default_headers(headers);
+        if let Some(proxy) = self.proxy.as_ref() {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    fn resolve_api_keys(&self, keys: &HashMap<String, Credentials>) -> anyhow::Result<Vec<String>> {
+        let env = self.backend.api_key_env();
+        let api_keys = match (self.api_key.as_ref(), env.and_then(|env| keys.get(env))) {
+            (Some(credentials), _) | (None, Some(credentials)) => credentials.resolve()?,
+            (None, None) => env
+                .and_then(|env| std::env::var(env).ok())
+                .into_iter()
+                .collect(),
+        };
+
+        if !api_keys.is_empty() {
+            Ok(api_keys)
+        } else if !self.backend.requires_api_key() {
+            Ok(vec![String::new()])
+        } else {
+            Err(anyhow::anyhow!(
+                "no api_key set and {} is not defined",
+                env.unwrap_or_default()
+            ))
+        }
+    }
+
+    // one agent per api key
+    pub fn agents(
+        &self,
+        model: &str,
+    ) -> anyhow::Result<Vec<AgentBuilder<CompletionModelHandle<'static>>>> {
+        self.api_keys
+            .iter()
+            .map(|api_key| self.agent(model, api_key))
+            .collect()
+    }
+
+    fn agent(
+        &self,
+        model: &str,
+        api_key: &str,
+    ) -> anyhow::Result<AgentBuilder<CompletionModelHandle<'static>>> {
+        let http_client = self
+            .http_client
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("provider not compiled"))?;
+        let base_url = self.base_url.as_deref();
+
+        // every rig client builder has the same shape, except for ollama that takes no key
+        macro_rules! agent {
+            ($client:ty, $builder:expr) => {{
+                let mut builder = $builder.custom_client(http_client);
+                if let Some(base_url) = base_url {
+                    builder = builder.base_url(base_url);
+                }
+                let client: $client = builder.build()?;
+                AgentBuilder::new(metered(CompletionClient::completion_model(&client, model)))
+            }};
+        }
+
+        let agent = match self.backend {
+            Backend::OpenAI => agent!(openai::Client, openai::Client::builder(api_key)),
+            Backend::OpenAICompatible => {
+                let mut builder = openai::Client::builder(api_key).custom_client(http_client);
+                if let Some(base_url) = base_url {
+                    builder = builder.base_url(base_url);
+                }
+                // rig defaults to the responses api, which most compatible servers don't implement
+                let client: openai::Client = builder.build()?;
+                let model = CompletionClient::completion_model(&client, model).completions_api();
+                AgentBuilder::new(metered(model))
+            }
+            Backend::Anthropic => agent!(anthropic::Client, anthropic::Client::builder(api_key)),
+            Backend::Gemini => agent!(gemini::Client, gemini::Client::builder(api_key)),
+            Backend::Ollama => agent!(ollama::Client, ollama::Client::builder()),
+            Backend::OpenRouter => {
+                agent!(openrouter::Client, openrouter::Client::builder(api_key))
+            }
+            Backend::Groq => agent!(groq::Client, groq::Client::builder(api_key)),
+            Backend::DeepSeek => agent!(deepseek::Client, deepseek::Client::builder(api_key)),
+            Backend::Mistral => agent!(mistral::Client, mistral::Client::builder(api_key)),
+            Backend::Xai => agent!(xai::Client, xai::Client::builder(api_key)),
+            Backend::Together => agent!(together::Client, together::Client::builder(api_key)),
+            Backend::Perplexity => {
+                agent!(perplexity::Client, perplexity::Client::builder(api_key))
+            }
+        };
+
+        Ok(agent)
+    }
+}
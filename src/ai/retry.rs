@@ -0,0 +1,313 @@
+use std::time::Duration;
+
+use rig::completion::CompletionError;
+use serde::Deserialize;
+use serde_json::Value;
+
+// times a model is asked again after an error that may go away, when the action doesn't say
+pub(super) const DEFAULT_RETRIES: u32 = 2;
+// seconds a model can go without sending anything, when the action doesn't set a timeout
+pub(super) const DEFAULT_TIMEOUT: u64 = 60;
+
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// what to ask when the model of an action fails
+#[derive(Deserialize, Clone)]
+pub(crate) struct Fallback {
+    pub provider: String,
+    pub model: String,
+}
+
+// how long to wait before the given retry, doubling every time
+pub(super) fn backoff(retry: u32) -> Duration {
+    FIRST_BACKOFF
+        .saturating_mul(1 << retry.min(16))
+        .min(MAX_BACKOFF)
+}
+
+// how a request failed before any status came back
+#[derive(Clone, Copy)]
+enum Transport {
+    Timeout,
+    Connect,
+    Connection,
+    Other,
+}
+
+// why a model failed in plain words, and if asking again could work
+pub(super) struct Failure {
+    pub message: String,
+    pub retryable: bool,
+}
+
+impl Failure {
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            message: format!("no response for {}s", timeout.as_secs()),
+            retryable: true,
+        }
+    }
+
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        // rig errors wrap the http ones, the first one found says the most
+        for cause in error.chain() {
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                return Self::from_http(e);
+            }
+            if let Some(CompletionError::ProviderError(body)) = cause.downcast_ref() {
+                return Self::from_provider(body);
+            }
+        }
+        Self {
+            message: error.to_string(),
+            retryable: false,
+        }
+    }
+
+    fn from_http(error: &reqwest::Error) -> Self {
+        if let Some(status) = error.status() {
+            return Self::from_status(status.as_u16(), None);
+        }
+        let transport = if error.is_timeout() {
+            Transport::Timeout
+        } else if error.is_connect() {
+            Transport::Connect
+        } else if error.is_body() || error.is_request() {
+            Transport::Connection
+        } else {
+            Transport::Other
+        };
+        Self::from_transport(transport, &error.to_string())
+    }
+
+    fn from_transport(transport: Transport, error: &str) -> Self {
+        let message = match transport {
+            Transport::Timeout => "the request timed out".to_string(),
+            Transport::Connect => format!("could not connect: {}", error),
+            Transport::Connection => format!("the connection failed: {}", error),
+            Transport::Other => {
+                return Self {
+                    message: error.to_string(),
+                    retryable: false,
+                };
+            }
+        };
+        Self {
+            message,
+            retryable: true,
+        }
+    }
+
+    // providers report errors as the status followed by the body, or just the body
+    fn from_provider(body: &str) -> Self {
+        let (status, body) = match body.split_once(' ') {
+            Some((code, rest)) if code.len() == 3 => match code.parse::<u16>() {
+                Ok(status) => (Some(status), rest.split_once(": ").map_or(rest, |(_, b)| b)),
+                Err(_) => (None, body),
+            },
+            _ => (None, body),
+        };
+        let json: Option<Value> = serde_json::from_str(body).ok();
+        let error = json.as_ref().map(|json| json.get("error").unwrap_or(json));
+        let detail = error
+            .and_then(|error| error.get("message"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| body.trim().chars().take(200).collect());
+
+        // gemini puts the status in the body, anthropic only names the error
+        let status = status
+            .or_else(|| {
+                error
+                    .and_then(|error| error.get("code"))
+                    .and_then(Value::as_u64)
+                    .map(|code| code as u16)
+            })
+            .or_else(|| {
+                match error
+                    .and_then(|error| error.get("type"))
+                    .and_then(Value::as_str)
+                {
+                    Some("rate_limit_error") => Some(429),
+                    Some("overloaded_error") => Some(529),
+                    Some("api_error") => Some(500),
+                    Some("authentication_error") => Some(401),
+                    Some("permission_error") => Some(403),
+                    Some("invalid_request_error") => Some(400),
+                    Some("not_found_error") => Some(404),
+                    _ => None,
+                }
+            });
+
+        match status {
+            Some(status) => Self::from_status(status, Some(detail)),
+            None => Self {
+                message: detail,
+                retryable: false,
+            },
+        }
+    }
+
+    fn from_status(status: u16, detail: Option<String>) -> Self {
+        let (what, retryable) = match status {
+            429 => ("rate limited", true),
+            408 => ("the request timed out", true),
+            529 => ("the provider is overloaded", true),
+            500..=599 => ("the provider failed", true),
+            401 | 403 => ("not authorized, check the api key", false),
+            404 => ("not found, check the model name", false),
+            _ => ("the request was rejected", false),
+        };
+        let mut message = format!("{} ({})", what, status);
+        if let Some(detail) = detail.filter(|detail| !detail.is_empty()) {
+            message = format!("{}: {}", message, detail);
+        }
+        Self { message, retryable }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider_error(body: &str) -> Failure {
+        Failure::from_error(&anyhow::Error::new(CompletionError::ProviderError(
+            body.to_string(),
+        )))
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_max() {
+        for (retry, seconds) in [(0, 1), (1, 2), (2, 4), (3, 8), (4, 16), (5, 30), (64, 30)] {
+            assert_eq!(
+                backoff(retry),
+                Duration::from_secs(seconds),
+                "retry {}",
+                retry
+            );
+        }
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        for (status, retryable) in [
+            (429, true),
+            (408, true),
+            (500, true),
+            (502, true),
+            (503, true),
+            (529, true),
+            (400, false),
+            (401, false),
+            (403, false),
+            (404, false),
+            (422, false),
+        ] {
+            let failure = Failure::from_status(status, None);
+            assert_eq!(failure.retryable, retryable, "status {}", status);
+            assert!(failure.message.contains(&status.to_string()));
+        }
+        assert_eq!(
+            Failure::from_status(401, Some("bad key".to_string())).message,
+            "not authorized, check the api key (401): bad key"
+        );
+    }
+
+    #[test]
+    fn classifies_provider_errors() {
+        for (body, retryable, message) in [
+            // openai and compatible servers: the status, its reason and the body
+            (
+                r#"429 Too Many Requests: {"error":{"message":"slow down","type":"requests"}}"#,
+                true,
+                "rate limited (429): slow down",
+            ),
+            (
+                "503 Service Unavailable: upstream unavailable",
+                true,
+                "the provider failed (503): upstream unavailable",
+            ),
+            (
+                r#"401 Unauthorized: {"error":{"message":"invalid api key"}}"#,
+                false,
+                "not authorized, check the api key (401): invalid api key",
+            ),
+            // gemini: the status is in the body
+            (
+                r#"{"error":{"code":500,"message":"internal"}}"#,
+                true,
+                "the provider failed (500): internal",
+            ),
+            // anthropic: only the type of error
+            (
+                r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+                true,
+                "the provider is overloaded (529): Overloaded",
+            ),
+            (
+                r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#,
+                false,
+                "not authorized, check the api key (401): invalid x-api-key",
+            ),
+            (
+                r#"{"type":"error","error":{"type":"invalid_request_error","message":"bad"}}"#,
+                false,
+                "the request was rejected (400): bad",
+            ),
+            // nothing to go by
+            ("something broke", false, "something broke"),
+        ] {
+            let failure = provider_error(body);
+            assert_eq!(failure.retryable, retryable, "{}", body);
+            assert_eq!(failure.message, message);
+        }
+    }
+
+    #[test]
+    fn finds_the_cause_in_the_chain() {
+        let error = anyhow::Error::new(CompletionError::ProviderError(
+            "429 Too Many Requests: later".to_string(),
+        ))
+        .context("streaming failed");
+        assert!(Failure::from_error(&error).retryable);
+
+        let failure = Failure::from_error(&anyhow::anyhow!("model not compiled"));
+        assert!(!failure.retryable);
+        assert_eq!(failure.message, "model not compiled");
+    }
+
+    #[test]
+    fn timeouts_are_retryable() {
+        let failure = Failure::timeout(Duration::from_secs(60));
+        assert!(failure.retryable);
+        assert_eq!(failure.message, "no response for 60s");
+    }
+
+    #[test]
+    fn connection_errors_are_retryable() {
+        let error = "error sending request: connection refused";
+        for (transport, retryable, message) in [
+            (
+                Transport::Timeout,
+                true,
+                "the request timed out".to_string(),
+            ),
+            (
+                Transport::Connect,
+                true,
+                format!("could not connect: {}", error),
+            ),
+            (
+                Transport::Connection,
+                true,
+                format!("the connection failed: {}", error),
+            ),
+            (Transport::Other, false, error.to_string()),
+        ] {
+            let failure = Failure::from_transport(transport, error);
+            assert_eq!(failure.retryable, retryable);
+            assert_eq!(failure.message, message);
+        }
+    }
+}
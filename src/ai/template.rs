@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+// variables that are filled in when the action is triggered
+pub(crate) const BUILTIN_VARIABLES: &[&str] = &["text", "date", "time", "previous_clipboard"];
+
+enum Part {
+    Literal(String),
+    Variable(String),
+}
+
+// a prompt with {{name}} placeholders
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find(OPEN) {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let after_open = &rest[start + OPEN.len()..];
+            let end = after_open.find(CLOSE).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "unclosed {} at offset {}",
+                    OPEN,
+                    source.len() - rest.len() + start
+                )
+            })?;
+            let name = after_open[..end].trim();
+            if name.is_empty() {
+                return Err(anyhow::anyhow!("empty variable name"));
+            }
+            parts.push(Part::Variable(name.to_string()));
+            rest = &after_open[end + CLOSE.len()..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable(name) => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    pub fn uses(&self, name: &str) -> bool {
+        self.variables().any(|var| var == name)
+    }
+
+    // make sure every placeholder is either a builtin or a user defined variable
+    pub fn validate(&self, vars: &HashMap<String, String>) -> anyhow::Result<()> {
+        for name in self.variables() {
+            if !BUILTIN_VARIABLES.contains(&name) && !vars.contains_key(name) {
+                return Err(anyhow::anyhow!(
+                    "unknown variable {}{}{}",
+                    OPEN,
+                    name,
+                    CLOSE
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self, values: &HashMap<&str, String>) -> String {
+        let mut rendered = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                // validated at compile time, missing values are optional builtins
+                Part::Variable(name) => {
+                    if let Some(value) = values.get(name.as_str()) {
+                        rendered.push_str(value);
+                    }
+                }
+            }
+        }
+        rendered
+    }
+
+    // fill in the user variables and the builtins, date and time are left empty without
+    // now, prompts without a placeholder for the text get it appended
+    pub fn render_prompt(
+        &self,
+        vars: &HashMap<String, String>,
+        text: Option<&str>,
+        previous_clipboard: Option<&str>,
+        now: Option<NaiveDateTime>,
+    ) -> String {
+        let mut values: HashMap<&str, String> = vars
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        if let Some(now) = now {
+            values.insert("date", now.format("%Y-%m-%d").to_string());
+            values.insert("time", now.format("%H:%M").to_string());
+        }
+        if let Some(text) = text {
+            values.insert("text", text.to_string());
+        }
+        if let Some(previous_clipboard) = previous_clipboard {
+            values.insert("previous_clipboard", previous_clipboard.to_string());
+        }
+
+        let mut prompt = self.render(&values);
+        // as they always did
+        if !self.uses("text")
+            && let Some(text) = text
+        {
+            prompt = prompt + "\n\n" + text;
+        }
+        prompt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> HashMap<String, String> {
+        HashMap::from([("language".to_string(), "French".to_string())])
+    }
+
+    fn render(source: &str, text: Option<&str>, previous_clipboard: Option<&str>) -> String {
+        Template::parse(source)
+            .unwrap()
+            .render_prompt(&vars(), text, previous_clipboard, None)
+    }
+
+    #[test]
+    fn renders_variables() {
+        assert_eq!(
+            render("Translate to {{language}}:\n{{text}}", Some("hello"), None),
+            "Translate to French:\nhello"
+        );
+        assert_eq!(
+            render("{{ language }}{{language}}", None, None),
+            "FrenchFrench"
+        );
+        assert_eq!(render("no variables", None, None), "no variables");
+    }
+
+    #[test]
+    fn appends_text_without_placeholder() {
+        assert_eq!(
+            render("Summarize:", Some("some text"), None),
+            "Summarize:\n\nsome text"
+        );
+        assert_eq!(render("Summarize:", None, None), "Summarize:");
+        assert_eq!(render("<t>{{text}}</t>", Some("x"), None), "<t>x</t>");
+    }
+
+    #[test]
+    fn renders_previous_clipboard() {
+        assert_eq!(
+            render(
+                "{{previous_clipboard}} -> {{text}}",
+                Some("new"),
+                Some("old")
+            ),
+            "old -> new"
+        );
+        // optional, there may be no previous clipboard
+        assert_eq!(
+            render("{{previous_clipboard}} -> {{text}}", Some("new"), None),
+            " -> new"
+        );
+    }
+
+    #[test]
+    fn renders_date_and_time() {
+        let template = Template::parse("{{date}} {{time}}").unwrap();
+        let now = chrono::NaiveDate::from_ymd_opt(2024, 3, 5)
+            .unwrap()
+            .and_hms_opt(9, 7, 30)
+            .unwrap();
+        assert_eq!(
+            template.render_prompt(&vars(), None, None, Some(now)),
+            "2024-03-05 09:07"
+        );
+        assert_eq!(template.render_prompt(&vars(), None, None, None), " ");
+    }
+
+    #[test]
+    fn validates_variables() {
+        let vars = vars();
+        for source in [
+            "{{text}} {{date}} {{time}} {{previous_clipboard}}",
+            "{{language}}",
+        ] {
+            assert!(Template::parse(source).unwrap().validate(&vars).is_ok());
+        }
+        let error = Template::parse("{{unknown}}")
+            .unwrap()
+            .validate(&vars)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "unknown variable {{unknown}}");
+    }
+
+    #[test]
+    fn rejects_malformed_placeholders() {
+        let error = Template::parse("Fix {{text").err().unwrap();
+        assert_eq!(error.to_string(), "unclosed {{ at offset 4");
+        let error = Template::parse("a {{ }} b").err().unwrap();
+        assert_eq!(error.to_string(), "empty variable name");
+    }
+}
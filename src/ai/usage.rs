@@ -0,0 +1,596 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{Datelike, NaiveDate, TimeZone};
+use futures::StreamExt;
+use rig::{
+    client::completion::CompletionModelHandle,
+    completion::{
+        CompletionError, CompletionModel, CompletionRequest, CompletionResponse, GetTokenUsage,
+        Usage,
+    },
+    message::{Message, Reasoning, UserContent},
+    streaming::{RawStreamingChoice, StreamedAssistantContent, StreamingCompletionResponse},
+};
+use serde::{Deserialize, Serialize};
+
+// price of a model in dollars per million tokens
+#[derive(Deserialize, Clone, Copy)]
+pub(crate) struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+pub(crate) struct Limits {
+    // spending past this many dollars shows a warning
+    pub soft: Option<f64>,
+    // spending past this many dollars blocks the actions using a model
+    pub hard: Option<f64>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+pub(crate) struct Budget {
+    #[serde(default)]
+    pub daily: Limits,
+    #[serde(default)]
+    pub monthly: Limits,
+}
+
+impl Budget {
+    pub fn is_set(&self) -> bool {
+        [self.daily, self.monthly]
+            .iter()
+            .any(|limits| limits.soft.is_some() || limits.hard.is_some())
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct Config {
+    // by model name, or by provider/model when the same model costs differently across
+    // providers
+    #[serde(default)]
+    pub pricing: HashMap<String, Price>,
+    #[serde(default)]
+    pub budget: Budget,
+    // records older than this are dropped, except those of the current month that the
+    // monthly budget needs, 0 keeps them forever
+    #[serde(default = "default_max_age_days")]
+    pub max_age_days: u64,
+}
+
+fn default_max_age_days() -> u64 {
+    90
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pricing: HashMap::new(),
+            budget: Budget::default(),
+            max_age_days: default_max_age_days(),
+        }
+    }
+}
+
+impl Config {
+    fn price(&self, provider: &str, model: &str) -> Option<Price> {
+        self.pricing
+            .get(&format!("{}/{}", provider, model))
+            .or_else(|| self.pricing.get(model))
+            .copied()
+    }
+}
+
+// a completion, one json entry per line in the usage file
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Record {
+    // unix timestamp in seconds
+    pub timestamp: u64,
+    pub action: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub latency_ms: u64,
+    // in dollars, none if the model has no price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    // the provider didn't report the usage, tokens were guessed from the text length
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+}
+
+#[derive(Default, Clone, Copy)]
+pub(crate) struct Totals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub latency_ms: u64,
+    pub cost: f64,
+    // requests of models without a price, not part of the cost
+    pub unpriced: u64,
+}
+
+impl Totals {
+    fn add(&mut self, record: &Record) {
+        self.requests += 1;
+        self.input_tokens += record.input_tokens;
+        self.output_tokens += record.output_tokens;
+        self.latency_ms += record.latency_ms;
+        match record.cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced += 1,
+        }
+    }
+
+    pub fn average_latency(&self) -> Duration {
+        Duration::from_millis(self.latency_ms / self.requests.max(1))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Row {
+    pub action: String,
+    pub provider: String,
+    pub model: String,
+    pub totals: Totals,
+}
+
+#[derive(Default, Clone)]
+pub(crate) struct Period {
+    pub totals: Totals,
+    // by action, provider and model, most expensive first
+    pub rows: Vec<Row>,
+}
+
+impl Period {
+    fn add(&mut self, record: &Record) {
+        self.totals.add(record);
+        let row = match self.rows.iter_mut().position(|row| {
+            row.action == record.action
+                && row.provider == record.provider
+                && row.model == record.model
+        }) {
+            Some(idx) => &mut self.rows[idx],
+            None => {
+                self.rows.push(Row {
+                    action: record.action.clone(),
+                    provider: record.provider.clone(),
+                    model: record.model.clone(),
+                    totals: Totals::default(),
+                });
+                self.rows.last_mut().unwrap()
+            }
+        };
+        row.totals.add(record);
+    }
+
+    fn sort(&mut self) {
+        self.rows.sort_by(|a, b| {
+            b.totals
+                .cost
+                .total_cmp(&a.totals.cost)
+                .then(b.totals.requests.cmp(&a.totals.requests))
+        });
+    }
+}
+
+// spending of the current day and month, in local time
+#[derive(Default, Clone)]
+pub(crate) struct Summary {
+    pub today: Period,
+    pub month: Period,
+}
+
+// the summary of the usage file as of when it was last read or written
+struct Tally {
+    summary: Summary,
+    // the day the summary is for
+    date: NaiveDate,
+    // size of the file, it was written by another instance if it changed
+    len: u64,
+}
+
+impl Tally {
+    fn add(&mut self, record: &Record) {
+        match record_date(record) {
+            Some(date) if date == self.date => {
+                self.summary.today.add(record);
+                self.summary.month.add(record);
+            }
+            Some(date) if same_month(date, self.date) => self.summary.month.add(record),
+            _ => {}
+        }
+    }
+
+    // start over when the day or the month changed since the summary was made
+    fn roll_over(&mut self, today: NaiveDate) {
+        if today == self.date {
+            return;
+        }
+        if !same_month(today, self.date) {
+            self.summary.month = Period::default();
+        }
+        self.summary.today = Period::default();
+        self.date = today;
+    }
+}
+
+// where completions are accounted, shared by everything that runs actions
+#[derive(Clone)]
+pub(crate) struct Ledger {
+    path: PathBuf,
+    config: Config,
+    // read from the file when first needed, kept up to date as completions are recorded
+    tally: Arc<Mutex<Option<Tally>>>,
+}
+
+impl Ledger {
+    pub fn new(path: PathBuf, config: Config) -> Self {
+        Self {
+            path,
+            config,
+            tally: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn record(&self, record: &Record) -> anyhow::Result<()> {
+        let mut tally = self.tally.lock().unwrap();
+        let before = self.file_len();
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        let len = file.metadata()?.len();
+
+        match tally.as_mut() {
+            // nothing else wrote to the file since it was read, so only this record is missing
+            Some(current) if current.len == before => {
+                current.roll_over(chrono::Local::now().date_naive());
+                current.add(record);
+                current.len = len;
+            }
+            _ => *tally = None,
+        }
+        Ok(())
+    }
+
+    fn file_len(&self) -> u64 {
+        std::fs::metadata(&self.path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0)
+    }
+
+    // the tally of today, read again if the file changed behind its back
+    fn current<'a>(&self, tally: &'a mut Option<Tally>) -> anyhow::Result<&'a mut Tally> {
+        let stale = tally
+            .as_ref()
+            .is_none_or(|current| current.len != self.file_len());
+        if stale {
+            *tally = Some(self.load()?);
+        }
+        let current = tally.as_mut().unwrap();
+        current.roll_over(chrono::Local::now().date_naive());
+        Ok(current)
+    }
+
+    // read the whole file, dropping the records that are too old for it
+    fn load(&self) -> anyhow::Result<Tally> {
+        let mut tally = Tally {
+            summary: Summary::default(),
+            date: chrono::Local::now().date_naive(),
+            len: 0,
+        };
+        if !self.path.exists() {
+            return Ok(tally);
+        }
+
+        let data = std::fs::read_to_string(&self.path)?;
+        let min_timestamp = now().saturating_sub(self.config.max_age_days * 86400);
+        let mut kept = String::new();
+        let mut pruned = false;
+        for (idx, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = match serde_json::from_str::<Record>(line) {
+                Ok(record) => record,
+                Err(e) => {
+                    eprintln!("skipping usage line {}: {}", idx + 1, e);
+                    kept.push_str(line);
+                    kept.push('\n');
+                    continue;
+                }
+            };
+            let current_month =
+                record_date(&record).is_some_and(|date| same_month(date, tally.date));
+            if self.config.max_age_days > 0 && record.timestamp < min_timestamp && !current_month {
+                pruned = true;
+                continue;
+            }
+            tally.add(&record);
+            kept.push_str(line);
+            kept.push('\n');
+        }
+
+        // rewrite the whole file only if something was dropped
+        if pruned {
+            std::fs::write(&self.path, kept)?;
+        }
+        tally.len = self.file_len();
+        Ok(tally)
+    }
+
+    pub fn summary(&self) -> anyhow::Result<Summary> {
+        let mut tally = self.tally.lock().unwrap();
+        let mut summary = self.current(&mut tally)?.summary.clone();
+        summary.today.sort();
+        summary.month.sort();
+        Ok(summary)
+    }
+
+    // the budgets the spending so far went over, as messages, with a flag telling if
+    // the budget is a hard one
+    pub fn exceeded(&self, summary: &Summary) -> Vec<(bool, String)> {
+        let budget = self.config.budget;
+        let mut exceeded = Vec::new();
+        for (name, limits, spent) in [
+            ("daily", budget.daily, summary.today.totals.cost),
+            ("monthly", budget.monthly, summary.month.totals.cost),
+        ] {
+            for (hard, limit) in [(true, limits.hard), (false, limits.soft)] {
+                if let Some(limit) = limit
+                    && spent >= limit
+                {
+                    exceeded.push((
+                        hard,
+                        format!(
+                            "{} budget of ${:.2} exceeded, ${:.2} spent",
+                            name, limit, spent
+                        ),
+                    ));
+                    // the soft limit says nothing more once the hard one is hit
+                    break;
+                }
+            }
+        }
+        exceeded
+    }
+
+    // fails if a hard budget is exceeded, returns the soft budgets exceeded otherwise
+    pub fn check_budget(&self) -> anyhow::Result<Vec<String>> {
+        if !self.config.budget.is_set() {
+            return Ok(Vec::new());
+        }
+
+        let mut tally = self.tally.lock().unwrap();
+        let summary = &self.current(&mut tally)?.summary;
+        let mut warnings = Vec::new();
+        for (hard, message) in self.exceeded(summary) {
+            if hard {
+                return Err(anyhow::anyhow!("{}", message));
+            }
+            warnings.push(message);
+        }
+        Ok(warnings)
+    }
+}
+
+// what the completions of an action are accounted to
+#[derive(Clone)]
+pub(super) struct Account {
+    pub ledger: Ledger,
+    pub action: String,
+    pub provider: String,
+    pub model: String,
+}
+
+impl Account {
+    // record a completion, usage is guessed from the prompt and response when the
+    // provider didn't report it
+    pub fn record(&self, usage: Usage, latency: Duration, prompt: &Message, response: &str) {
+        let estimated = usage.input_tokens == 0 && usage.output_tokens == 0;
+        let (input_tokens, output_tokens) = if estimated {
+            (
+                estimate_tokens(&message_text(prompt)),
+                estimate_tokens(response),
+            )
+        } else {
+            (usage.input_tokens, usage.output_tokens)
+        };
+        let cost = self
+            .ledger
+            .config
+            .price(&self.provider, &self.model)
+            .map(|price| {
+                (input_tokens as f64 * price.input + output_tokens as f64 * price.output)
+                    / 1_000_000.0
+            });
+
+        let record = Record {
+            timestamp: now(),
+            action: self.action.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            input_tokens,
+            output_tokens,
+            latency_ms: latency.as_millis() as u64,
+            cost,
+            estimated,
+        };
+        if let Err(e) = self.ledger.record(&record) {
+            eprintln!(
+                "could not write usage to {}: {}",
+                self.ledger.path.display(),
+                e
+            );
+        }
+    }
+}
+
+pub(crate) fn format_tokens(tokens: u64) -> String {
+    if tokens >= 1_000_000 {
+        format!("{:.1}M", tokens as f64 / 1_000_000.0)
+    } else if tokens >= 1_000 {
+        format!("{:.1}k", tokens as f64 / 1_000.0)
+    } else {
+        tokens.to_string()
+    }
+}
+
+// small amounts need more digits to be told apart
+pub(crate) fn format_cost(cost: f64) -> String {
+    if cost < 1.0 {
+        format!("${:.4}", cost)
+    } else {
+        format!("${:.2}", cost)
+    }
+}
+
+fn record_date(record: &Record) -> Option<NaiveDate> {
+    chrono::Local
+        .timestamp_opt(record.timestamp as i64, 0)
+        .single()
+        .map(|time| time.date_naive())
+}
+
+fn same_month(a: NaiveDate, b: NaiveDate) -> bool {
+    a.year() == b.year() && a.month() == b.month()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// roughly four characters per token for english text
+fn estimate_tokens(text: &str) -> u64 {
+    text.chars().count().div_ceil(4) as u64
+}
+
+fn message_text(message: &Message) -> String {
+    match message {
+        Message::User { content } => content
+            .iter()
+            .filter_map(|content| match content {
+                UserContent::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Message::Assistant { .. } => String::new(),
+    }
+}
+
+tokio::task_local! {
+    static METER: Meter;
+}
+
+// adds up the usage reported by the completions made while it's in scope
+#[derive(Clone, Default)]
+pub(super) struct Meter(Arc<Mutex<Usage>>);
+
+impl Meter {
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        METER.scope(self.clone(), future).await
+    }
+
+    pub fn usage(&self) -> Usage {
+        *self.0.lock().unwrap()
+    }
+
+    fn add(usage: Usage) {
+        let _ = METER.try_with(|meter| {
+            let mut total = meter.0.lock().unwrap();
+            total.input_tokens += usage.input_tokens;
+            total.output_tokens += usage.output_tokens;
+            total.total_tokens += usage.total_tokens;
+        });
+    }
+}
+
+// rig drops the usage of streamed responses when models are type erased, this reads it
+// from the final response before it's lost
+#[derive(Clone)]
+struct Metered<M>(M);
+
+impl<M> CompletionModel for Metered<M>
+where
+    M: CompletionModel + 'static,
+    M::StreamingResponse: 'static,
+{
+    type Response = M::Response;
+    type StreamingResponse = M::StreamingResponse;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        let response = self.0.completion(request).await?;
+        Meter::add(response.usage);
+        Ok(response)
+    }
+
+    async fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+        let response = self.0.stream(request).await?;
+        // rig hands out all the reasoning so far with every chunk
+        let mut reasoning_len = 0;
+        let stream = response.map(move |item| {
+            item.map(|content| match content {
+                StreamedAssistantContent::Text(text) => RawStreamingChoice::Message(text.text),
+                StreamedAssistantContent::ToolCall(call) => RawStreamingChoice::ToolCall {
+                    id: call.id,
+                    call_id: call.call_id,
+                    name: call.function.name,
+                    arguments: call.function.arguments,
+                },
+                StreamedAssistantContent::Reasoning(Reasoning { id, reasoning }) => {
+                    let reasoning = reasoning.concat();
+                    let delta = reasoning
+                        .get(reasoning_len..)
+                        .unwrap_or_default()
+                        .to_string();
+                    reasoning_len = reasoning.len();
+                    RawStreamingChoice::Reasoning {
+                        id,
+                        reasoning: delta,
+                    }
+                }
+                StreamedAssistantContent::Final(response) => {
+                    if let Some(usage) = response.token_usage() {
+                        Meter::add(usage);
+                    }
+                    RawStreamingChoice::FinalResponse(response)
+                }
+            })
+        });
+        Ok(StreamingCompletionResponse::stream(Box::pin(stream)))
+    }
+}
+
+pub(super) fn metered<M>(model: M) -> CompletionModelHandle<'static>
+where
+    M: CompletionModel + 'static,
+    M::StreamingResponse: 'static,
+{
+    CompletionModelHandle {
+        inner: Arc::new(Metered(model)),
+    }
+}
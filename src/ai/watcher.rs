@@ -0,0 +1,31 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// notify when any of the given files changes, the modification time is polled rather
+// than watching the inode since editors often replace the file instead of writing it
+pub(crate) fn start_watcher(paths: Vec<PathBuf>, tx: mpsc::Sender<()>) {
+    std::thread::spawn(move || {
+        let mut last: Vec<Option<SystemTime>> = paths.iter().map(|p| modified(p)).collect();
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let current: Vec<Option<SystemTime>> = paths.iter().map(|p| modified(p)).collect();
+            if current != last {
+                last = current;
+                if tx.send(()).is_err() {
+                    // receiver is gone
+                    break;
+                }
+            }
+        }
+    });
+}
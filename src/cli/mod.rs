@@ -0,0 +1,205 @@
+use std::{
+    collections::HashMap,
+    io::{IsTerminal, Read, Write},
+    sync::mpsc,
+};
+
+use crate::{ai, clipboard};
+
+#[cfg(unix)]
+mod remote;
+#[cfg(unix)]
+pub(crate) use remote::{call_remote, show_remote, trigger_remote};
+
+// build the action input from a file, stdin or the clipboard
+fn read_input(
+    action: &ai::Action,
+    config: &ai::Config,
+    input: Option<&str>,
+) -> anyhow::Result<ai::ActionInput> {
+    let input = match read_text(input)? {
+        Some(text) => ai::ActionInput {
+            text: Some(text),
+            html: None,
+            rtf: None,
+            image: None,
+            files: None,
+            previous_text: None,
+            args: HashMap::new(),
+            refresh: false,
+        },
+        None => {
+            let clipboard = clipboard::get_clipboard()?;
+            ai::ActionInput {
+                text: clipboard.text,
+                html: clipboard.html,
+                rtf: clipboard.rtf,
+                image: if action.images { clipboard.image } else { None },
+                files: if action.files && !clipboard.files.is_empty() {
+                    Some(ai::read_files(&clipboard.files, &config.files))
+                } else {
+                    None
+                },
+                previous_text: None,
+                args: HashMap::new(),
+                refresh: false,
+            }
+        }
+    };
+
+    if input.is_empty() {
+        return Err(anyhow::anyhow!("no input text"));
+    }
+
+    Ok(input)
+}
+
+// the input text from a file or stdin, none if the clipboard should be used instead
+fn read_text(input: Option<&str>) -> anyhow::Result<Option<String>> {
+    match input {
+        Some("-") => Ok(Some(read_stdin()?)),
+        Some(path) => {
+            Ok(Some(std::fs::read_to_string(path).map_err(|e| {
+                anyhow::anyhow!("could not read {}: {}", path, e)
+            })?))
+        }
+        // piped input wins over the clipboard, unless there's none like when stdin is
+        // /dev/null for a keybinding or a service
+        None if !std::io::stdin().is_terminal() => {
+            let text = read_stdin()?;
+            Ok(if text.is_empty() { None } else { Some(text) })
+        }
+        None => Ok(None),
+    }
+}
+
+fn read_stdin() -> anyhow::Result<String> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
+
+// run an action without the UI, streaming the result to stdout or copying it to the
+// clipboard once complete
+pub(crate) fn run_action(
+    config_path: &str,
+    label: &str,
+    input: Option<&str>,
+    model: Option<String>,
+    to_clipboard: bool,
+    refresh: bool,
+) -> anyhow::Result<()> {
+    let mut config = ai::Config::load(config_path)?;
+    let index = config
+        .action_index(label)
+        .ok_or_else(|| anyhow::anyhow!("no action labeled {}", label))?;
+    if let Some(model) = model {
+        config.actions[index].model = model;
+    }
+    config.compile()?;
+
+    let action = &config.actions[index];
+    let mut input = read_input(action, &config, input)?;
+    input.refresh = refresh;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let (events_tx, events_rx) = mpsc::channel();
+    runtime.spawn(action.task(0, &input, events_tx)?);
+
+    // pipelines stream every step, for them and local actions only the final response is
+    // printed
+    let stream = !to_clipboard && action.streams();
+    let mut stdout = std::io::stdout();
+    // cached responses come whole, without chunks
+    let mut streamed = false;
+    let mut attempts = 0;
+    for event in events_rx {
+        match event {
+            ai::ActionEvent::Chunk(_, chunk) => {
+                if stream {
+                    stdout.write_all(chunk.as_bytes())?;
+                    stdout.flush()?;
+                    streamed = true;
+                }
+            }
+            ai::ActionEvent::Step(_, label, _) => {
+                eprintln!("{} done", label);
+                // the next step asks its own model
+                attempts = 0;
+            }
+            ai::ActionEvent::Cached(_) => eprintln!("cached response"),
+            ai::ActionEvent::Attempt(_, model) => {
+                attempts += 1;
+                // the first one is the action's own model, anything after is a retry or a
+                // fallback
+                if attempts > 1 {
+                    if streamed {
+                        writeln!(stdout)?;
+                        streamed = false;
+                    }
+                    eprintln!("asking {}", model);
+                }
+            }
+            ai::ActionEvent::Warning(_, warning) => eprintln!("warning: {}", warning),
+            ai::ActionEvent::Response(_, response, _) => {
+                if to_clipboard {
+                    clipboard::set_clipboard_text(response)?;
+                    return Ok(());
+                }
+                if !streamed {
+                    stdout.write_all(response.as_bytes())?;
+                }
+                if !response.ends_with('\n') {
+                    writeln!(stdout)?;
+                }
+                return Ok(());
+            }
+            ai::ActionEvent::Error(_, error) => return Err(error),
+        }
+    }
+
+    Err(anyhow::anyhow!("{} ended without a response", action.label))
+}
+
+// print the usage of the current day and month
+pub(crate) fn print_stats(config_path: &str) -> anyhow::Result<()> {
+    let config = ai::Config::load(config_path)?;
+    let ledger = config.ledger();
+    let summary = ledger.summary()?;
+
+    for (title, period) in [("today", &summary.today), ("this month", &summary.month)] {
+        let totals = &period.totals;
+        println!(
+            "{}: {} in {} requests, {} tokens in, {} out",
+            title,
+            ai::format_cost(totals.cost),
+            totals.requests,
+            ai::format_tokens(totals.input_tokens),
+            ai::format_tokens(totals.output_tokens)
+        );
+        for row in period.rows.iter() {
+            let cost = if row.totals.unpriced == row.totals.requests {
+                "-".to_string()
+            } else {
+                ai::format_cost(row.totals.cost)
+            };
+            println!(
+                "  {:<24} {:<32} {:>5} {:>8} {:>8} {:>6.1}s {:>10}",
+                row.action,
+                format!("{}/{}", row.provider, row.model),
+                row.totals.requests,
+                ai::format_tokens(row.totals.input_tokens),
+                ai::format_tokens(row.totals.output_tokens),
+                row.totals.average_latency().as_secs_f32(),
+                cost
+            );
+        }
+    }
+
+    for (_, message) in ledger.exceeded(&summary) {
+        eprintln!("warning: {}", message);
+    }
+    Ok(())
+}
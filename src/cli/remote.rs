@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use super::read_text;
+use crate::{ai::Config, control};
+
+// how often a triggered job is polled for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+fn connect() -> anyhow::Result<control::Client> {
+    control::Client::connect(&Config::default_socket_file())
+        .map_err(|e| anyhow::anyhow!("could not connect to a running clipbud: {}", e))
+}
+
+// call a method on the running instance and print what it returns
+pub(crate) fn call_remote(method: &str, params: Value) -> anyhow::Result<()> {
+    let result = connect()?.call(method, params)?;
+    match result {
+        Value::Null => println!("nothing yet"),
+        // the output alone is what scripts want from a result
+        Value::Object(ref job) if method == "result" => {
+            println!(
+                "{}",
+                job.get("output")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+            )
+        }
+        result => println!("{}", serde_json::to_string_pretty(&result)?),
+    }
+    Ok(())
+}
+
+// bring up the window of the running instance, fails if there's none
+pub(crate) fn show_remote() -> anyhow::Result<()> {
+    connect()?.call("show", json!({}))?;
+    Ok(())
+}
+
+// run an action in the running instance, on the given text or on its clipboard, and
+// wait for the result
+pub(crate) fn trigger_remote(label: &str, input: Option<&str>) -> anyhow::Result<()> {
+    let mut client = connect()?;
+
+    let mut params = json!({ "label": label });
+    if let Some(text) = read_text(input)? {
+        params["text"] = json!(text);
+    }
+    let id = client
+        .call("run", params)?
+        .get("job")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow::anyhow!("no job id returned"))?;
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let job = client.call("job", json!({ "id": id }))?;
+        let output = job
+            .get("output")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        match job.get("state").and_then(Value::as_str) {
+            Some("running") => continue,
+            Some("done") => {
+                println!("{}", output);
+                if job
+                    .get("review")
+                    .and_then(Value::as_bool)
+                    .unwrap_or_default()
+                {
+                    eprintln!("awaiting review in the window, nothing was pasted yet");
+                }
+                return Ok(());
+            }
+            Some(state) => return Err(anyhow::anyhow!("job {}: {}", state, output)),
+            None => return Err(anyhow::anyhow!("invalid job state")),
+        }
+    }
+}
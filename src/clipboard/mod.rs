@@ -1,11 +1,63 @@
 use clipboard_rs::{
     Clipboard, ClipboardContext, ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext,
-    WatcherShutdown,
+    ContentFormat, WatcherShutdown, common::RustImage,
 };
-use std::sync::{Mutex, mpsc};
+use percent_encoding::percent_decode_str;
+use std::sync::{Arc, Mutex, mpsc};
+
+const THUMBNAIL_SIZE: u32 = 128;
+const FILE_URI_PREFIX: &str = "file://";
+
+#[derive(Clone)]
+pub(crate) struct Image {
+    pub width: u32,
+    pub height: u32,
+    // png encoded, this is what gets sent to the models
+    pub png: Arc<Vec<u8>>,
+    // small rgba preview for the UI
+    pub thumbnail_size: [usize; 2],
+    pub thumbnail_rgba: Arc<Vec<u8>>,
+}
+
+impl Image {
+    fn from_clipboard(ctx: &ClipboardContext) -> anyhow::Result<Self> {
+        let image = ctx
+            .get_image()
+            .map_err(|e| anyhow::anyhow!("failed to get clipboard image: {}", e))?;
+        let (width, height) = image.get_size();
+        let png = image
+            .to_png()
+            .map_err(|e| anyhow::anyhow!("failed to encode clipboard image: {}", e))?;
+        let thumbnail = image
+            .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+            .and_then(|thumbnail| thumbnail.to_rgba8())
+            .map_err(|e| anyhow::anyhow!("failed to create thumbnail: {}", e))?;
+
+        Ok(Self {
+            width,
+            height,
+            png: Arc::new(png.get_bytes().to_vec()),
+            thumbnail_size: [thumbnail.width() as usize, thumbnail.height() as usize],
+            thumbnail_rgba: Arc::new(thumbnail.into_raw()),
+        })
+    }
+}
 
 pub(crate) struct Event {
-    pub text: String,
+    pub text: Option<String>,
+    pub html: Option<String>,
+    pub rtf: Option<String>,
+    pub image: Option<Image>,
+    // local paths of copied files
+    pub files: Vec<String>,
+}
+
+// file managers on linux copy file:// uris, other platforms give us plain paths
+fn file_path(uri: &str) -> String {
+    match uri.strip_prefix(FILE_URI_PREFIX) {
+        Some(path) => percent_decode_str(path).decode_utf8_lossy().to_string(),
+        None => uri.to_string(),
+    }
 }
 
 pub(crate) struct Observer {
@@ -20,17 +72,78 @@ impl Observer {
     }
 }
 
+// everything we can use from the clipboard, none if there's nothing usable
+fn read(ctx: &ClipboardContext) -> Option<Event> {
+    let mut text = None;
+    let mut html = None;
+    let mut rtf = None;
+    let mut image = None;
+    let mut files = Vec::new();
+
+    if ctx.has(ContentFormat::Files) {
+        match ctx.get_files() {
+            Ok(uris) => files = uris.iter().map(|uri| file_path(uri)).collect(),
+            Err(e) => eprintln!("read clipboard, error = {}", e),
+        }
+    }
+
+    if ctx.has(ContentFormat::Image) {
+        match Image::from_clipboard(ctx) {
+            Ok(img) => image = Some(img),
+            Err(e) => eprintln!("read clipboard, error = {}", e),
+        }
+    }
+
+    if ctx.has(ContentFormat::Text) {
+        match ctx.get_text() {
+            Ok(txt) => text = Some(txt),
+            Err(e) => eprintln!("read clipboard, error = {}", e),
+        }
+    }
+
+    // rich formats are captured alongside plain text so actions can pick the one they want
+    if ctx.has(ContentFormat::Html) {
+        match ctx.get_html() {
+            Ok(data) => html = Some(data),
+            Err(e) => eprintln!("read clipboard, error = {}", e),
+        }
+    }
+
+    if ctx.has(ContentFormat::Rtf) {
+        match ctx.get_rich_text() {
+            Ok(data) => rtf = Some(data),
+            Err(e) => eprintln!("read clipboard, error = {}", e),
+        }
+    }
+
+    if text.is_some() || html.is_some() || image.is_some() || !files.is_empty() {
+        Some(Event {
+            text,
+            html,
+            rtf,
+            image,
+            files,
+        })
+    } else {
+        None
+    }
+}
+
 impl ClipboardHandler for Observer {
     fn on_clipboard_change(&mut self) {
-        match self.ctx.get_text() {
-            Ok(txt) => {
-                let _ = self.event_tx.send(Event { text: txt });
-            }
-            Err(e) => eprintln!("on_clipboard_change, error = {}", e),
+        if let Some(event) = read(&self.ctx) {
+            let _ = self.event_tx.send(event);
         }
     }
 }
 
+// current clipboard contents, for when there's no observer running
+pub(crate) fn get_clipboard() -> anyhow::Result<Event> {
+    let ctx = ClipboardContext::new()
+        .map_err(|e| anyhow::anyhow!("Failed to get clipboard context: {}", e))?;
+    read(&ctx).ok_or_else(|| anyhow::anyhow!("clipboard is empty"))
+}
+
 pub(crate) fn set_clipboard_text(text: String) -> anyhow::Result<()> {
     if let Ok(ctx) = ClipboardContext::new() {
         if let Err(e) = ctx.set_text(text) {
@@ -0,0 +1,186 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::mpsc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+// json-rpc 2.0 error codes
+pub(crate) const PARSE_ERROR: i64 = -32700;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+pub(crate) const APPLICATION_ERROR: i64 = -32000;
+
+// how long a client waits for the running instance to answer
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Error {
+    pub code: i64,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RpcResponse {
+    jsonrpc: String,
+    id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<Error>,
+}
+
+// a call received on the socket, the UI answers it through reply
+pub(crate) struct Request {
+    pub method: String,
+    pub params: Value,
+    pub reply: mpsc::Sender<Result<Value, Error>>,
+}
+
+// listen on the control socket, one json-rpc request per line, and forward the calls
+// to the UI
+pub(crate) fn start_server(path: &Path, request_tx: mpsc::Sender<Request>) -> anyhow::Result<()> {
+    // the socket is reachable by anyone who can enter its directory from the moment it's
+    // bound, so the directory is locked down first
+    if let Some(parent) = path.parent() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)?;
+        std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+    }
+    // we're the only instance, anything there is left over from a previous run
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    println!("control socket listening on {}", path.display());
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let request_tx = request_tx.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve(stream, request_tx) {
+                            eprintln!("control connection error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("control socket error: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn serve(stream: UnixStream, request_tx: mpsc::Sender<Request>) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<RpcRequest>(&line) {
+            Ok(request) => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                request_tx.send(Request {
+                    method: request.method,
+                    params: request.params,
+                    reply: reply_tx,
+                })?;
+                let outcome = reply_rx
+                    .recv()
+                    .unwrap_or_else(|_| Err(Error::new(APPLICATION_ERROR, "no reply")));
+                response(request.id, outcome)
+            }
+            Err(e) => response(Value::Null, Err(Error::new(PARSE_ERROR, e.to_string()))),
+        };
+
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+    }
+    Ok(())
+}
+
+fn response(id: Value, outcome: Result<Value, Error>) -> RpcResponse {
+    let (result, error) = match outcome {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    RpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result,
+        error,
+    }
+}
+
+// connection to a running instance
+pub(crate) struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl Client {
+    // fails if no instance is listening
+    pub fn connect(path: &Path) -> anyhow::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            next_id: 1,
+        })
+    }
+
+    pub fn call(&mut self, method: &str, params: Value) -> anyhow::Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        writeln!(self.writer, "{}", request)?;
+
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        let response: RpcResponse = serde_json::from_str(&line)?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(anyhow::anyhow!("{} (code {})", error.message, error.code)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Ok(Value::Null),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct Config {
+    // maximum number of entries to keep, 0 disables history
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    // entries older than this are dropped, 0 keeps them forever
+    #[serde(default = "default_max_age_days")]
+    pub max_age_days: u64,
+}
+
+fn default_max_entries() -> usize {
+    500
+}
+
+fn default_max_age_days() -> u64 {
+    30
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_entries: default_max_entries(),
+            max_age_days: default_max_age_days(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Entry {
+    // unix timestamp in seconds
+    pub timestamp: u64,
+    pub text: String,
+    // label of the action that produced this text, none if it was copied by the user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+}
+
+impl Entry {
+    pub fn age(&self) -> String {
+        let secs = now().saturating_sub(self.timestamp);
+        if secs < 60 {
+            "just now".to_string()
+        } else if secs < 3600 {
+            format!("{}m ago", secs / 60)
+        } else if secs < 86400 {
+            format!("{}h ago", secs / 3600)
+        } else {
+            format!("{}d ago", secs / 86400)
+        }
+    }
+
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.text.to_lowercase().contains(&query)
+            || self
+                .action
+                .as_ref()
+                .is_some_and(|action| action.to_lowercase().contains(&query))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// clipboard and action results history, persisted as one json entry per line
+pub(crate) struct History {
+    path: PathBuf,
+    config: Config,
+    // oldest first
+    entries: Vec<Entry>,
+}
+
+impl History {
+    pub fn open(path: &Path, config: Config) -> anyhow::Result<Self> {
+        let mut entries = Vec::new();
+
+        if path.exists() {
+            println!("loading history from {}", path.display());
+            for (idx, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<Entry>(line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => eprintln!("skipping history line {}: {}", idx + 1, e),
+                }
+            }
+        }
+
+        let mut history = Self {
+            path: path.to_path_buf(),
+            config,
+            entries,
+        };
+
+        // apply retention to what was loaded and compact the file
+        history.prune();
+        history.save()?;
+
+        Ok(history)
+    }
+
+    // apply new retention settings, e.g. after the configuration is reloaded
+    pub fn configure(&mut self, config: Config) {
+        self.config = config;
+        if self.prune()
+            && let Err(e) = self.save()
+        {
+            eprintln!("could not write history to {}: {}", self.path.display(), e);
+        }
+    }
+
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn record_clipboard(&mut self, text: &str) {
+        // our own pastes come back from the clipboard observer, don't record them twice
+        if self.entries.last().is_some_and(|last| last.text == text) {
+            return;
+        }
+        self.record(Entry {
+            timestamp: now(),
+            text: text.to_string(),
+            action: None,
+        });
+    }
+
+    pub fn record_action(&mut self, action: &str, text: &str) {
+        self.record(Entry {
+            timestamp: now(),
+            text: text.to_string(),
+            action: Some(action.to_string()),
+        });
+    }
+
+    fn record(&mut self, entry: Entry) {
+        if self.config.max_entries == 0 {
+            return;
+        }
+
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("could not serialize history entry: {}", e);
+                return;
+            }
+        };
+        self.entries.push(entry);
+
+        // rewrite the whole file only if something was dropped, append otherwise
+        let result = if self.prune() {
+            self.save()
+        } else {
+            self.append(&line)
+        };
+        if let Err(e) = result {
+            eprintln!("could not write history to {}: {}", self.path.display(), e);
+        }
+    }
+
+    // returns true if any entry was removed
+    fn prune(&mut self) -> bool {
+        let before = self.entries.len();
+
+        if self.config.max_age_days > 0 {
+            let min_timestamp = now().saturating_sub(self.config.max_age_days * 86400);
+            self.entries
+                .retain(|entry| entry.timestamp >= min_timestamp);
+        }
+
+        if self.entries.len() > self.config.max_entries {
+            let excess = self.entries.len() - self.config.max_entries;
+            self.entries.drain(..excess);
+        }
+
+        self.entries.len() != before
+    }
+
+    fn append(&self, line: &str) -> anyhow::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let mut data = String::new();
+        for entry in self.entries.iter() {
+            data.push_str(&serde_json::to_string(entry)?);
+            data.push('\n');
+        }
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+}
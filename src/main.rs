@@ -1,21 +1,100 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 use std::sync::mpsc;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use single_instance::SingleInstance;
 
 use crate::ai::Config;
 
 mod ai;
+mod cli;
 mod clipboard;
+#[cfg(unix)]
+mod control;
+mod history;
 mod ui;
 
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Validate the configuration file and exit.
+    Check,
+    /// Run an action without the UI and print its result.
+    Run {
+        /// Label of the action to run.
+        label: String,
+        /// File to read the input text from, - for stdin. Defaults to stdin when piped,
+        /// the clipboard otherwise.
+        #[arg(short, long)]
+        input: Option<String>,
+        /// Use this model instead of the action's one.
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Copy the result to the clipboard instead of printing it.
+        #[arg(long)]
+        clipboard: bool,
+        /// Ask the model again even if the response is cached.
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Print the token usage and cost of today and this month.
+    Stats,
+    #[command(flatten)]
+    Remote(Remote),
+}
+
+// the commands forwarded to the running instance
+#[derive(Debug, Subcommand)]
+enum Remote {
+    /// Show the window of the running instance.
+    Show,
+    /// List the actions of the running instance.
+    Actions,
+    /// Run an action in the running instance and print its result.
+    Trigger {
+        /// Label of the action to run.
+        label: String,
+        /// File to read the input text from, - for stdin. Defaults to stdin when piped,
+        /// the clipboard of the running instance otherwise.
+        #[arg(short, long)]
+        input: Option<String>,
+    },
+    /// Print the latest result of the running instance.
+    Result,
+    /// Stop reacting to clipboard changes in the running instance.
+    Pause,
+    /// Resume reacting to clipboard changes in the running instance.
+    Resume,
+}
+
 #[derive(Debug, Parser)]
 struct Arguments {
     #[arg(short, long)]
     config: Option<String>,
     #[arg(long)]
     start_delay: Option<u64>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[cfg(unix)]
+fn forward(command: Remote) -> anyhow::Result<()> {
+    use serde_json::json;
+
+    match command {
+        Remote::Show => cli::show_remote(),
+        Remote::Actions => cli::call_remote("actions", json!({})),
+        Remote::Trigger { label, input } => cli::trigger_remote(&label, input.as_deref()),
+        Remote::Result => cli::call_remote("result", json!({})),
+        Remote::Pause => cli::call_remote("pause", json!({})),
+        Remote::Resume => cli::call_remote("resume", json!({})),
+    }
+}
+
+#[cfg(not(unix))]
+fn forward(_command: Remote) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!(
+        "controlling a running instance is only supported on unix"
+    ))
 }
 
 fn main() -> anyhow::Result<()> {
@@ -26,6 +105,59 @@ fn main() -> anyhow::Result<()> {
         std::thread::sleep(std::time::Duration::from_millis(start_delay));
     }
 
+    let config_path = args.config.unwrap_or(
+        ai::Config::default_config_file()
+            .to_str()
+            .unwrap()
+            .to_string(),
+    );
+
+    match args.command {
+        Some(Command::Check) => match ai::check_file(&config_path) {
+            Ok(true) => return Ok(()),
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("could not check {}: {}", config_path, e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Run {
+            label,
+            input,
+            model,
+            clipboard,
+            refresh,
+        }) => {
+            if let Err(e) = cli::run_action(
+                &config_path,
+                &label,
+                input.as_deref(),
+                model,
+                clipboard,
+                refresh,
+            ) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Stats) => {
+            if let Err(e) = cli::print_stats(&config_path) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        None => {}
+        Some(Command::Remote(command)) => {
+            if let Err(e) = forward(command) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+    }
+
     // create user data if needed
     if let Err(e) = ai::Config::create_user_data() {
         ui::dialogs::show_error(format!("Could not create user data: {}", e));
@@ -34,19 +166,17 @@ fn main() -> anyhow::Result<()> {
 
     // make sure we're the only instance running
     if !SingleInstance::new(Config::default_lock_file().to_str().unwrap())?.is_single() {
+        // bring up the running one instead, if it can be reached
+        #[cfg(unix)]
+        if cli::show_remote().is_ok() {
+            return Ok(());
+        }
         ui::dialogs::show_error("Clipboard Buddy is already running.".to_string());
         return Ok(());
     }
 
     // load config
-    let config = match ai::Config::from_file(
-        &args.config.unwrap_or(
-            ai::Config::default_config_file()
-                .to_str()
-                .unwrap()
-                .to_string(),
-        ),
-    ) {
+    let config = match ai::Config::from_file(&config_path) {
         Ok(config) => config,
         Err(e) => {
             ui::dialogs::show_error(format!("Could not load configuration: {}", e));
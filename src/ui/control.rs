@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use eframe::egui;
+use serde_json::{Value, json};
+
+use super::UI;
+use crate::{ai, control};
+
+// review is set while the response waits to be accepted in the window
+fn job_json(job: &ai::Job, review: bool) -> Value {
+    json!({
+        "id": job.id,
+        "label": job.label,
+        "state": job.state.as_str(),
+        "cached": job.cached,
+        "model": job.model,
+        "output": job.output,
+        "elapsed_ms": job.elapsed().as_millis() as u64,
+        "review": review,
+    })
+}
+
+fn invalid_params(message: &str) -> control::Error {
+    control::Error::new(control::INVALID_PARAMS, message)
+}
+
+impl UI {
+    fn awaiting_review(&self, id: ai::JobId) -> bool {
+        self.pending_reviews.contains_key(&id)
+            || self.review.as_ref().is_some_and(|review| review.job == id)
+    }
+
+    // answer the calls received on the control socket
+    pub(super) fn on_control_request(&mut self, ctx: &egui::Context) {
+        while let Ok(request) = self.control_rx.try_recv() {
+            let outcome = self.handle_control_request(ctx, &request.method, &request.params);
+            let _ = request.reply.send(outcome);
+        }
+    }
+
+    fn handle_control_request(
+        &mut self,
+        ctx: &egui::Context,
+        method: &str,
+        params: &Value,
+    ) -> Result<Value, control::Error> {
+        match method {
+            "show" => {
+                self.show_window(ctx);
+                Ok(json!(true))
+            }
+            "actions" => Ok(Value::Array(
+                self.config
+                    .actions
+                    .iter()
+                    .map(|action| {
+                        json!({
+                            "label": action.label,
+                            "key": action.key,
+                            "model": action.model,
+                            "provider": action.provider,
+                        })
+                    })
+                    .collect(),
+            )),
+            "run" => {
+                let label = params
+                    .get("label")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid_params("missing label"))?;
+                let index = self.config.action_index(label).ok_or_else(|| {
+                    control::Error::new(
+                        control::APPLICATION_ERROR,
+                        format!("no action labeled {}", label),
+                    )
+                })?;
+                let action = &self.config.actions[index];
+
+                // the given text or what's in the clipboard
+                let mut input = match params.get("text").and_then(Value::as_str) {
+                    Some(text) => ai::ActionInput {
+                        text: Some(text.to_string()),
+                        html: None,
+                        rtf: None,
+                        image: None,
+                        files: None,
+                        previous_text: self.clipboard_text.clone(),
+                        args: HashMap::new(),
+                        refresh: false,
+                    },
+                    None => self.clipboard_input(action),
+                };
+                input.refresh = params
+                    .get("refresh")
+                    .and_then(Value::as_bool)
+                    .unwrap_or_default();
+                // for builtins that take arguments
+                if let Some(args) = params.get("args").and_then(Value::as_object) {
+                    input.args = args
+                        .iter()
+                        .filter_map(|(name, value)| {
+                            value
+                                .as_str()
+                                .map(|value| (name.clone(), value.to_string()))
+                        })
+                        .collect();
+                }
+                if input.is_empty() {
+                    return Err(control::Error::new(
+                        control::APPLICATION_ERROR,
+                        "no input text",
+                    ));
+                }
+
+                let id = self.executor.submit(action, &input);
+                // reviewed in the window before it's pasted, as when run from there
+                if action.review && action.paste {
+                    self.pending_reviews.insert(id, input);
+                }
+                Ok(json!({ "job": id }))
+            }
+            "job" => {
+                let id = params
+                    .get("id")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| invalid_params("missing id"))?;
+                let review = self.awaiting_review(id);
+                self.executor
+                    .job(id)
+                    .map(|job| job_json(job, review))
+                    .ok_or_else(|| {
+                        control::Error::new(
+                            control::APPLICATION_ERROR,
+                            format!("unknown job {}", id),
+                        )
+                    })
+            }
+            "result" => Ok(self
+                .executor
+                .jobs()
+                .iter()
+                .find(|job| !job.is_running())
+                .map(|job| job_json(job, self.awaiting_review(job.id)))
+                .unwrap_or(Value::Null)),
+            "pause" | "resume" => {
+                self.monitoring_paused = method == "pause";
+                println!(
+                    "clipboard monitoring {}",
+                    if self.monitoring_paused {
+                        "paused"
+                    } else {
+                        "resumed"
+                    }
+                );
+                Ok(json!({ "paused": self.monitoring_paused }))
+            }
+            _ => Err(control::Error::new(
+                control::METHOD_NOT_FOUND,
+                format!("unknown method {}", method),
+            )),
+        }
+    }
+}
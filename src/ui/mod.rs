@@ -1,19 +1,44 @@
 use eframe::egui;
 use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, hotkey::HotKey};
 use mouse_position::mouse_position::Mouse;
-use std::{str::FromStr, sync::mpsc};
+use std::{collections::HashMap, str::FromStr, sync::mpsc};
 use tray_icon::menu::MenuEvent;
 
 use crate::ai::{ButtonsWrap, Config};
+use crate::history::History;
 use crate::{ai, clipboard};
 
+#[cfg(unix)]
+mod control;
 pub(crate) mod dialogs;
+mod review;
 mod spinner;
+mod stats;
 mod tray;
+mod undo;
 
 const DEFAULT_WINDOW_SIZE: egui::Vec2 = egui::vec2(400.0, 200.0);
 const DEFAULT_WINDOW_OFFSET: f32 = 10.0;
 const DEFAULT_MAX_TEXTAREA_HEIGHT: f32 = 130.0;
+const HISTORY_PREVIEW_CHARS: usize = 60;
+
+// the model a job asked last, with the attempt once retries or fallbacks kicked in
+fn job_model(job: &ai::Job) -> Option<String> {
+    let model = job.model.as_ref()?;
+    Some(if job.is_running() && job.attempts > 1 {
+        format!("{} (attempt {})", model, job.attempts)
+    } else {
+        model.clone()
+    })
+}
+
+// arguments asked for before running a builtin that needs them
+struct ArgsForm {
+    // the action is looked up by label again on submit, a reload may have moved it
+    label: String,
+    values: Vec<(&'static str, String)>,
+}
+
 pub(crate) struct UI {
     config: Config,
 
@@ -23,20 +48,48 @@ pub(crate) struct UI {
     monitor_size: egui::Vec2,
 
     clipboard_text: Option<String>,
+    // what the clipboard text looks like, decides which actions are shown
+    clipboard_detection: Option<ai::Detection>,
+    previous_clipboard_text: Option<String>,
+    clipboard_html: Option<String>,
+    clipboard_rtf: Option<String>,
+    clipboard_image: Option<clipboard::Image>,
+    clipboard_files: Vec<String>,
+    // created lazily from the image thumbnail
+    clipboard_texture: Option<egui::TextureHandle>,
+
+    executor: ai::Executor,
+    // the job whose output is being shown, if any
+    focused_job: Option<ai::JobId>,
+    // inputs of the jobs whose response is reviewed before it's pasted
+    pending_reviews: HashMap<ai::JobId, ai::ActionInput>,
+    // the response being reviewed, if any
+    review: Option<review::Review>,
+    // pasted responses of this session, so they can be undone
+    undo_stack: undo::UndoStack,
+
+    // clipboard changes are ignored while paused
+    monitoring_paused: bool,
 
-    is_loading: bool,
-    loading_start_time: std::time::Instant,
-    current_action_label: String,
+    history: History,
+    show_history: bool,
+    history_query: String,
+
+    stats: stats::Stats,
+    show_stats: bool,
 
     // modal state
     show_error_modal: bool,
     error_message: String,
+    args_form: Option<ArgsForm>,
 
     clipboard_rx: mpsc::Receiver<clipboard::Event>,
-    action_response_rx: mpsc::Receiver<ai::ActionEvent>,
-    action_response_tx: mpsc::Sender<ai::ActionEvent>,
+    config_rx: mpsc::Receiver<()>,
+    #[cfg(unix)]
+    control_rx: mpsc::Receiver<crate::control::Request>,
     tray: tray::Tray,
-    _hotkey_manager: Option<GlobalHotKeyManager>,
+    hotkey_manager: Option<GlobalHotKeyManager>,
+    hotkey: Option<HotKey>,
 }
 
 impl UI {
@@ -45,48 +98,152 @@ impl UI {
         clipboard_rx: mpsc::Receiver<clipboard::Event>,
         config: Config,
     ) -> anyhow::Result<Self> {
-        let (action_response_tx, action_response_rx) = mpsc::channel();
+        let executor = ai::Executor::new()?;
+        let history = History::open(&Config::default_history_file(), config.history.clone())?;
 
         let tray = tray::build_tray_menu_icon()?;
 
-        let mut _hotkey_manager = None;
-        if let Some(hotkey) = config.hotkey.as_ref() {
-            let manager = GlobalHotKeyManager::new()?;
-            let hotkey = HotKey::from_str(hotkey)?;
-            println!("registering for hotkey: {}", hotkey);
-            manager.register(hotkey)?;
-            _hotkey_manager = Some(manager);
-        } else {
-            println!("registering for clipboard change")
-        }
+        // reload the configuration when it or the system preamble change
+        let (config_tx, config_rx) = mpsc::channel();
+        ai::start_watcher(
+            vec![config.path.clone(), Config::default_system_file()],
+            config_tx,
+        );
 
-        if let Some(theme) = config.theme.as_ref() {
-            match theme.as_str() {
-                "dark" => creation_context.egui_ctx.set_theme(egui::Theme::Dark),
-                "light" => creation_context.egui_ctx.set_theme(egui::Theme::Light),
-                "system" => {}
-                _ => return Err(anyhow::anyhow!("invalid theme: {}", theme)),
+        // let scripts and other instances drive this one
+        #[cfg(unix)]
+        let control_rx = {
+            let (control_tx, control_rx) = mpsc::channel();
+            if let Err(e) = crate::control::start_server(&Config::default_socket_file(), control_tx)
+            {
+                eprintln!("could not start control socket: {}", e);
             }
-        }
+            control_rx
+        };
 
-        Ok(Self {
+        Self::apply_theme(&creation_context.egui_ctx, &config);
+
+        let mut ui = Self {
             clipboard_text: None,
+            clipboard_detection: None,
+            previous_clipboard_text: None,
+            clipboard_html: None,
+            clipboard_rtf: None,
+            clipboard_image: None,
+            clipboard_files: Vec::new(),
+            clipboard_texture: None,
             window_visible: false,
             window_size: DEFAULT_WINDOW_SIZE,
             window_position: egui::pos2(0.0, 0.0),
             monitor_size: egui::vec2(1024.0, 768.0),
             config,
-            is_loading: false,
-            loading_start_time: std::time::Instant::now(),
-            current_action_label: String::new(),
+            executor,
+            focused_job: None,
+            pending_reviews: HashMap::new(),
+            review: None,
+            undo_stack: undo::UndoStack::default(),
+            monitoring_paused: false,
+            history,
+            show_history: false,
+            history_query: String::new(),
+            stats: stats::Stats::default(),
+            show_stats: false,
             show_error_modal: false,
             error_message: String::new(),
+            args_form: None,
             clipboard_rx,
-            action_response_rx,
-            action_response_tx,
+            config_rx,
+            #[cfg(unix)]
+            control_rx,
             tray,
-            _hotkey_manager,
-        })
+            hotkey_manager: None,
+            hotkey: None,
+        };
+        ui.register_hotkey()?;
+        ui.refresh_stats();
+
+        Ok(ui)
+    }
+
+    fn apply_theme(ctx: &egui::Context, config: &Config) {
+        // validated when the config is compiled
+        match config.theme.as_deref() {
+            Some("dark") => ctx.set_theme(egui::Theme::Dark),
+            Some("light") => ctx.set_theme(egui::Theme::Light),
+            Some("system") => ctx.set_theme(egui::ThemePreference::System),
+            _ => {}
+        }
+    }
+
+    // register the configured hotkey, replacing the previous one if any
+    fn register_hotkey(&mut self) -> anyhow::Result<()> {
+        let hotkey = match self.config.hotkey.as_ref() {
+            Some(hotkey) => Some(HotKey::from_str(hotkey)?),
+            None => None,
+        };
+        if hotkey == self.hotkey {
+            return Ok(());
+        }
+
+        if let (Some(manager), Some(previous)) = (self.hotkey_manager.as_ref(), self.hotkey) {
+            manager.unregister(previous)?;
+        }
+        self.hotkey = None;
+
+        if let Some(hotkey) = hotkey {
+            if self.hotkey_manager.is_none() {
+                self.hotkey_manager = Some(GlobalHotKeyManager::new()?);
+            }
+            println!("registering for hotkey: {}", hotkey);
+            self.hotkey_manager.as_ref().unwrap().register(hotkey)?;
+            self.hotkey = Some(hotkey);
+        } else {
+            println!("registering for clipboard change")
+        }
+
+        Ok(())
+    }
+
+    // parse and compile the configuration file again, the new configuration is swapped
+    // in only if it's valid so that a broken file doesn't stop the running one
+    fn reload_config(&mut self, ctx: &egui::Context) {
+        let config = match Config::from_file(&self.config.path.to_string_lossy()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("could not reload config: {}", e);
+                self.error_message = format!(
+                    "❌ Failed to reload config, keeping the previous one: {}",
+                    e
+                );
+                self.show_error_modal = true;
+                return;
+            }
+        };
+        if let Some(hotkey) = config.hotkey.as_ref()
+            && let Err(e) = HotKey::from_str(hotkey)
+        {
+            self.error_message = format!(
+                "❌ Failed to reload config, keeping the previous one: invalid hotkey {}: {}",
+                hotkey, e
+            );
+            self.show_error_modal = true;
+            return;
+        }
+
+        println!("configuration reloaded");
+        self.config = config;
+        self.history.configure(self.config.history.clone());
+        // prices and budgets may have changed
+        self.refresh_stats();
+        Self::apply_theme(ctx, &self.config);
+        ctx.send_viewport_cmd(egui::ViewportCommand::Resizable(matches!(
+            self.config.wrap_buttons,
+            ButtonsWrap::Horizontal
+        )));
+        if let Err(e) = self.register_hotkey() {
+            self.error_message = format!("❌ Failed to register hotkey: {}", e);
+            self.show_error_modal = true;
+        }
     }
 
     fn show_error_modal(&mut self, ctx: &egui::Context) {
@@ -114,6 +271,55 @@ impl UI {
         }
     }
 
+    fn show_args_form(&mut self, ctx: &egui::Context) {
+        let Some(form) = self.args_form.as_mut() else {
+            return;
+        };
+        let mut run = false;
+        let mut open = true;
+
+        egui::Window::new(form.label.as_str())
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("args_form").num_columns(2).show(ui, |ui| {
+                    for (idx, (name, value)) in form.values.iter_mut().enumerate() {
+                        ui.label(*name);
+                        let response = ui.text_edit_singleline(value);
+                        if idx == 0
+                            && !response.has_focus()
+                            && ctx.memory(|m| m.focused().is_none())
+                        {
+                            response.request_focus();
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.add_space(10.0);
+                run = ui.button("[Enter] Run").clicked()
+                    || ctx.input(|i| i.key_pressed(egui::Key::Enter));
+            });
+
+        if run {
+            let form = self.args_form.take().unwrap();
+            let Some(index) = self.config.action_index(&form.label) else {
+                self.error_message = format!("❌ {} was removed from the config", form.label);
+                self.show_error_modal = true;
+                return;
+            };
+            let args = form
+                .values
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect();
+            self.run_action(index, args, false);
+        } else if !open {
+            self.args_form = None;
+        }
+    }
+
     fn show_window(&mut self, ctx: &egui::Context) {
         // get current mouse position
         let (mouse_x, mouse_y) = match Mouse::get_mouse_position() {
@@ -139,8 +345,23 @@ impl UI {
         self.window_visible = true;
     }
 
+    // true if the job being shown is still running
+    fn is_loading(&self) -> bool {
+        self.focused_job
+            .and_then(|id| self.executor.job(id))
+            .is_some_and(|job| job.is_running())
+    }
+
+    fn cancel_job(&mut self, id: ai::JobId) {
+        self.executor.cancel(id);
+        self.pending_reviews.remove(&id);
+        if self.focused_job == Some(id) {
+            self.focused_job = None;
+        }
+    }
+
     fn hide_window(&mut self, ctx: &egui::Context) {
-        if !self.is_loading {
+        if !self.is_loading() {
             self.window_visible = false;
             self.show_error_modal = false;
             ctx.send_viewport_cmd_to(
@@ -151,9 +372,10 @@ impl UI {
         }
     }
 
-    fn render_spinner(&self, ui: &mut egui::Ui) {
+    fn render_spinner(&self, ui: &mut egui::Ui, job: &ai::Job) -> bool {
+        let mut cancel = false;
         // used for the spinner angle
-        let elapsed = self.loading_start_time.elapsed().as_secs_f32();
+        let elapsed = job.elapsed().as_secs_f32();
         // center the spinner and label vertically and horizontally
         let available_rect = ui.available_rect_before_wrap();
         ui.scope_builder(
@@ -161,7 +383,7 @@ impl UI {
                 .max_rect(available_rect)
                 .layout(egui::Layout::top_down(egui::Align::Center)),
             |ui| {
-                let content_height = spinner::LAYOUT_SIZE.y + 8.0 + 20.0;
+                let content_height = spinner::LAYOUT_SIZE.y + 8.0 + 20.0 + 8.0 + 20.0;
                 let vertical_offset = (available_rect.height() - content_height) / 2.0;
 
                 if vertical_offset > 0.0 {
@@ -175,53 +397,427 @@ impl UI {
                     },
                 );
                 ui.add_space(8.0);
-                ui.label(&self.current_action_label);
+                match job_model(job) {
+                    Some(model) => ui.label(format!("{} · {}", job.label, model)),
+                    None => ui.label(&job.label),
+                };
+                ui.add_space(8.0);
+                cancel = ui.button("[Esc] Cancel").clicked();
             },
         );
+        cancel
     }
 
-    fn render_actions(&mut self, ui: &mut egui::Ui) {
-        for action in self.config.actions.iter() {
-            if ui
-                .button(egui::RichText::new(action.button_text()))
-                .clicked()
-            {
-                if let Some(clipboard_text) = self.clipboard_text.as_ref() {
-                    self.is_loading = true;
-                    self.loading_start_time = std::time::Instant::now();
-                    self.current_action_label = action.label.clone();
-                    action.trigger(clipboard_text, self.action_response_tx.clone());
-                } else {
-                    self.show_error_modal = true;
-                    self.error_message = "❌ No clipboard text found".to_string();
-                }
+    fn render_job(&mut self, ui: &mut egui::Ui, id: ai::JobId) {
+        let Some(job) = self.executor.job(id) else {
+            // pruned, nothing left to show
+            self.focused_job = None;
+            return;
+        };
+
+        // nothing received yet, keep spinning
+        if job.is_running() && job.output.is_empty() && job.steps.is_empty() {
+            if self.render_spinner(ui, job) {
+                self.cancel_job(id);
             }
+            return;
         }
-    }
 
-    fn render_main(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
-        let mut clipboard_text = if let Some(text) = self.clipboard_text.as_ref() {
-            text.clone()
-        } else {
-            "❌ No clipboard text found.".to_string()
-        };
+        let mut cancel = false;
+        let mut back = false;
+        ui.horizontal(|ui| {
+            if job.is_running() && !job.steps.is_empty() {
+                ui.label(format!(
+                    "⏳ {} (step {}) ...",
+                    job.label,
+                    job.steps.len() + 1
+                ));
+                cancel = ui.button("[Esc] Cancel").clicked();
+            } else if job.is_running() {
+                ui.label(format!("⏳ {} ...", job.label));
+                if let Some(model) = job_model(job) {
+                    ui.weak(model);
+                }
+                cancel = ui.button("[Esc] Cancel").clicked();
+            } else {
+                ui.label(format!(
+                    "#{} {} ({}, {:.1}s)",
+                    job.id,
+                    job.label,
+                    job.state.as_str(),
+                    job.elapsed().as_secs_f32()
+                ));
+                if job.cached {
+                    ui.label("💾 cached")
+                        .on_hover_text("shift+trigger the action to skip the cache");
+                }
+                if let Some(model) = job_model(job) {
+                    ui.weak(model).on_hover_text("the model that answered");
+                }
+                back = ui.button("[Esc] Back").clicked();
+            }
+        });
 
+        // intermediate results of pipelines
+        for (idx, (label, output)) in job.steps.iter().enumerate() {
+            egui::CollapsingHeader::new(format!("✔ {}. {}", idx + 1, label))
+                .id_salt(("step", job.id, idx))
+                .show(ui, |ui| {
+                    let mut output = output.clone();
+                    egui::ScrollArea::vertical()
+                        .id_salt(("step_scroll", job.id, idx))
+                        .max_height(DEFAULT_MAX_TEXTAREA_HEIGHT)
+                        .show(ui, |ui| {
+                            ui.add(
+                                egui::TextEdit::multiline(&mut output)
+                                    .interactive(false)
+                                    .desired_width(f32::INFINITY),
+                            );
+                        });
+                });
+        }
+
+        let mut output = job.output.clone();
         egui::ScrollArea::vertical()
-            .max_height(DEFAULT_MAX_TEXTAREA_HEIGHT)
+            .max_height(ui.available_height())
+            .stick_to_bottom(true)
             .show(ui, |ui| {
                 ui.add_sized(
                     ui.available_size(),
-                    egui::TextEdit::multiline(&mut clipboard_text).interactive(false),
+                    egui::TextEdit::multiline(&mut output).interactive(false),
                 );
             });
 
+        if cancel {
+            self.cancel_job(id);
+        } else if back {
+            self.focused_job = None;
+        }
+    }
+
+    fn render_jobs(&mut self, ui: &mut egui::Ui) {
+        if self.executor.jobs().is_empty() {
+            return;
+        }
+
+        let mut cancel = None;
+        let mut view = None;
+        egui::CollapsingHeader::new(format!("Jobs ({} running)", self.executor.running()))
+            .id_salt("jobs")
+            .show(ui, |ui| {
+                for job in self.executor.jobs() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "#{} {} - {} {:.1}s",
+                            job.id,
+                            job.label,
+                            job.state.as_str(),
+                            job.elapsed().as_secs_f32()
+                        ));
+                        if job.cached {
+                            ui.weak("💾 cached");
+                        }
+                        if let Some(model) = job_model(job) {
+                            ui.weak(model);
+                        }
+                        if job.is_running() && ui.small_button("Cancel").clicked() {
+                            cancel = Some(job.id);
+                        }
+                        if ui.small_button("View").clicked() {
+                            view = Some(job.id);
+                        }
+                    });
+                }
+            });
+
+        if let Some(id) = cancel {
+            self.cancel_job(id);
+        }
+        if view.is_some() {
+            self.focused_job = view;
+        }
+    }
+
+    fn render_history(&mut self, ui: &mut egui::Ui) {
+        let search = ui.add(
+            egui::TextEdit::singleline(&mut self.history_query)
+                .hint_text("🔍 Search history ...")
+                .desired_width(f32::INFINITY),
+        );
+        search.request_focus();
+
+        let mut selected = None;
+        egui::ScrollArea::vertical()
+            .id_salt("history_scroll")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                // newest first
+                for entry in self
+                    .history
+                    .entries()
+                    .rev()
+                    .filter(|entry| entry.matches(&self.history_query))
+                {
+                    let first_line = entry.text.lines().next().unwrap_or_default();
+                    let mut preview: String =
+                        first_line.chars().take(HISTORY_PREVIEW_CHARS).collect();
+                    if preview.len() < entry.text.len() {
+                        preview.push_str(" ...");
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .selectable_label(false, preview)
+                            .on_hover_text(&entry.text)
+                            .clicked()
+                        {
+                            selected = Some(entry.text.clone());
+                        }
+                        let source = entry.action.as_deref().unwrap_or("copied");
+                        ui.weak(format!("{} · {}", source, entry.age()));
+                    });
+                }
+            });
+
+        // make the selected item the input of the next action
+        if let Some(text) = selected {
+            self.set_clipboard_text(text);
+            self.close_history();
+        }
+    }
+
+    // replace whatever was in the clipboard with plain text
+    fn set_clipboard_text(&mut self, text: String) {
+        self.update_clipboard_text(Some(text));
+        self.clipboard_html = None;
+        self.clipboard_rtf = None;
+        self.clipboard_image = None;
+        self.clipboard_files.clear();
+        self.clipboard_texture = None;
+    }
+
+    // keep track of the previous text for the {{previous_clipboard}} variable
+    fn update_clipboard_text(&mut self, text: Option<String>) {
+        if text.is_some() && text != self.clipboard_text {
+            self.previous_clipboard_text = self.clipboard_text.take();
+        }
+        self.clipboard_detection = text.as_deref().and_then(ai::detect);
+        self.clipboard_text = text;
+    }
+
+    // actions are hidden when their when: conditions don't match the clipboard text
+    fn is_action_visible(&self, action: &ai::Action) -> bool {
+        action.is_visible(
+            self.clipboard_detection.as_ref(),
+            self.clipboard_text.as_deref().unwrap_or_default(),
+        )
+    }
+
+    fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+        if self.show_stats {
+            self.close_history();
+            self.refresh_stats();
+        }
+    }
+
+    fn close_history(&mut self) {
+        self.show_history = false;
+        self.history_query.clear();
+    }
+
+    // the current clipboard contents as the given action wants them
+    fn clipboard_input(&self, action: &ai::Action) -> ai::ActionInput {
+        ai::ActionInput {
+            text: self.clipboard_text.clone(),
+            html: self.clipboard_html.clone(),
+            rtf: self.clipboard_rtf.clone(),
+            image: if action.images {
+                self.clipboard_image.clone()
+            } else {
+                None
+            },
+            files: if action.files && !self.clipboard_files.is_empty() {
+                Some(ai::read_files(&self.clipboard_files, &self.config.files))
+            } else {
+                None
+            },
+            previous_text: self.previous_clipboard_text.clone(),
+            args: HashMap::new(),
+            refresh: false,
+        }
+    }
+
+    // with refresh set cached responses are skipped
+    fn trigger_action(&mut self, index: usize, refresh: bool) {
+        let missing = self.config.actions[index].missing_args();
+        if !missing.is_empty() {
+            self.args_form = Some(ArgsForm {
+                label: self.config.actions[index].label.clone(),
+                values: missing
+                    .into_iter()
+                    .map(|name| (name, String::new()))
+                    .collect(),
+            });
+            return;
+        }
+        self.run_action(index, HashMap::new(), refresh);
+    }
+
+    fn run_action(&mut self, index: usize, args: HashMap<String, String>, refresh: bool) {
+        let action = &self.config.actions[index];
+        let mut input = self.clipboard_input(action);
+        input.args = args;
+        input.refresh = refresh;
+
+        if input.is_empty() {
+            self.show_error_modal = true;
+            self.error_message = if self.clipboard_image.is_some() {
+                format!("❌ {} does not accept images", action.label)
+            } else {
+                "❌ No clipboard text found".to_string()
+            };
+            return;
+        }
+
+        let id = self.executor.submit(action, &input);
+        // builtins are already done, no need for the spinner
+        if !action.is_builtin() {
+            self.focused_job = Some(id);
+        }
+        if action.review && action.paste {
+            self.pending_reviews.insert(id, input);
+        }
+    }
+
+    fn render_actions(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        for (index, action) in self.config.actions.iter().enumerate() {
+            if !self.is_action_visible(action) {
+                continue;
+            }
+            let mut text = action.button_text();
+            if action.images {
+                text = format!("🖼 {}", text);
+            }
+            if action.files {
+                text = format!("📁 {}", text);
+            }
+            if action.is_pipeline() {
+                text = format!("⛓ {}", text);
+            }
+            if action.is_command() {
+                text = format!("⚙ {}", text);
+            }
+            if action.is_builtin() {
+                text = format!("⚡ {}", text);
+            }
+            if action.format != ai::InputFormat::Plain {
+                text = format!("{} ({})", text, action.format.as_str());
+            }
+            let mut hover = format!("input format: {}", action.format.as_str());
+            if action.cache {
+                hover.push_str("\nshift+click to skip the cached response");
+            }
+            if ui
+                .button(egui::RichText::new(text))
+                .on_hover_text(hover)
+                .clicked()
+            {
+                clicked = Some((index, ui.input(|i| i.modifiers.shift)));
+            }
+        }
+
+        if let Some((index, refresh)) = clicked {
+            self.trigger_action(index, refresh);
+        }
+    }
+
+    fn render_image(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let Some(image) = self.clipboard_image.as_ref() else {
+            return;
+        };
+
+        let texture = self.clipboard_texture.get_or_insert_with(|| {
+            ctx.load_texture(
+                "clipboard_image",
+                egui::ColorImage::from_rgba_unmultiplied(
+                    image.thumbnail_size,
+                    &image.thumbnail_rgba,
+                ),
+                egui::TextureOptions::LINEAR,
+            )
+        });
+
+        ui.horizontal(|ui| {
+            ui.image((texture.id(), texture.size_vec2()));
+            ui.label(format!("🖼 {}x{}", image.width, image.height));
+        });
+    }
+
+    fn render_main(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        self.render_image(ui, ctx);
+
+        if !self.clipboard_files.is_empty() {
+            let names: Vec<&str> = self
+                .clipboard_files
+                .iter()
+                .map(|path| path.rsplit(['/', '\\']).next().unwrap_or(path))
+                .collect();
+            ui.label(format!(
+                "📁 {} file(s): {}",
+                self.clipboard_files.len(),
+                names.join(", ")
+            ))
+            .on_hover_text(self.clipboard_files.join("\n"));
+        }
+
+        // let the user know which formats are available to the actions
+        if self.clipboard_html.is_some() || self.clipboard_rtf.is_some() {
+            let mut formats = vec!["plain"];
+            if self.clipboard_html.is_some() {
+                formats.push("html");
+                formats.push("markdown");
+            }
+            if self.clipboard_rtf.is_some() {
+                formats.push("rtf");
+            }
+            ui.weak(format!("formats: {}", formats.join(" · ")));
+        }
+
+        if let Some(detection) = self.clipboard_detection.as_ref() {
+            ui.label(
+                egui::RichText::new(format!("🔎 {}", detection.badge()))
+                    .small()
+                    .strong(),
+            );
+        }
+
+        // an image on its own doesn't need the text area
+        if self.clipboard_text.is_some() || self.clipboard_image.is_none() {
+            let mut clipboard_text = if let Some(text) = self.clipboard_text.as_ref() {
+                text.clone()
+            } else {
+                "❌ No clipboard text found.".to_string()
+            };
+
+            egui::ScrollArea::vertical()
+                .max_height(DEFAULT_MAX_TEXTAREA_HEIGHT)
+                .show(ui, |ui| {
+                    ui.add_sized(
+                        ui.available_size(),
+                        egui::TextEdit::multiline(&mut clipboard_text).interactive(false),
+                    );
+                });
+        }
+
         ui.add_space(6.0);
 
         match self.config.wrap_buttons {
             ButtonsWrap::Horizontal => {
                 egui::ScrollArea::horizontal()
                     .id_salt("buttons_scroll")
-                    .auto_shrink([false, false])
+                    .auto_shrink([false, true])
                     .show(ui, |ui| {
                         ui.horizontal(|ui| {
                             self.render_actions(ui);
@@ -246,6 +842,8 @@ impl UI {
                 }
             }
         }
+
+        self.render_jobs(ui);
     }
 
     fn render(&mut self, ctx: &egui::Context) {
@@ -259,11 +857,43 @@ impl UI {
         if self.window_visible {
             egui::CentralPanel::default().show(ctx, |ui| {
                 ui.vertical(|ui| {
-                    ui.label(format!("📋 Clipboard Buddy v{}", env!("CARGO_PKG_VERSION")));
+                    ui.horizontal(|ui| {
+                        ui.label(format!("📋 Clipboard Buddy v{}", env!("CARGO_PKG_VERSION")));
+                        if self.monitoring_paused {
+                            ui.label("⏸ paused");
+                        }
+                        if !self.stats.warnings.is_empty() {
+                            ui.colored_label(ui.visuals().warn_fg_color, "⚠ budget")
+                                .on_hover_text(self.stats.warnings.join("\n"));
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui
+                                .selectable_label(self.show_history, "🕘 History")
+                                .clicked()
+                            {
+                                if self.show_history {
+                                    self.close_history();
+                                } else {
+                                    self.show_history = true;
+                                    self.show_stats = false;
+                                }
+                            }
+                            if ui.selectable_label(self.show_stats, "📊 Stats").clicked() {
+                                self.toggle_stats();
+                            }
+                            self.render_undo_buttons(ui);
+                        });
+                    });
                     ui.separator();
 
-                    if self.is_loading {
-                        self.render_spinner(ui);
+                    if let Some(id) = self.focused_job {
+                        self.render_job(ui, id);
+                    } else if self.review.is_some() {
+                        self.render_review(ui, ctx);
+                    } else if self.show_history {
+                        self.render_history(ui);
+                    } else if self.show_stats {
+                        self.render_stats(ui);
                     } else {
                         self.render_main(ui, ctx);
                     }
@@ -282,37 +912,65 @@ impl UI {
 
 impl UI {
     fn on_keypress(&mut self, ctx: &egui::Context) {
-        if self.is_loading {
+        // action keys only work from the main view
+        if self.focused_job.is_some()
+            || self.show_history
+            || self.show_stats
+            || self.args_form.is_some()
+            || self.review.is_some()
+        {
+            return;
+        }
+
+        // before the action keys, that would see the z otherwise
+        let (redo, undo) = ctx.input_mut(|i| {
+            let redo = i.consume_key(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::Z,
+            ) || i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y);
+            (redo, i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z))
+        });
+        if redo {
+            self.redo();
+            return;
+        }
+        if undo {
+            self.undo();
             return;
         }
 
         // check for action key presses
-        for action in self.config.actions.iter() {
-            if action.key.is_some()
-                && ctx.input(|i| {
-                    i.key_pressed(egui::Key::from_name(action.key.as_ref().unwrap()).unwrap())
-                })
-            {
-                if let Some(clipboard_text) = self.clipboard_text.as_ref() {
-                    self.is_loading = true;
-                    self.loading_start_time = std::time::Instant::now();
-                    self.current_action_label = action.label.clone();
-                    action.trigger(clipboard_text, self.action_response_tx.clone());
-                } else {
-                    self.show_error_modal = true;
-                    self.error_message = "❌ No clipboard text found".to_string();
-                }
-                break;
-            }
+        // keys are validated when the config is compiled
+        let pressed = self.config.actions.iter().position(|action| {
+            self.is_action_visible(action)
+                && action
+                    .key
+                    .as_deref()
+                    .and_then(egui::Key::from_name)
+                    .is_some_and(|key| ctx.input(|i| i.key_pressed(key)))
+        });
+        if let Some(index) = pressed {
+            let refresh = ctx.input(|i| i.modifiers.shift);
+            self.trigger_action(index, refresh);
         }
     }
 
     fn on_clibboard_change_or_hotkey(&mut self, ctx: &egui::Context) {
         let mut do_show = false;
 
-        // update clipboard text
-        if let Ok(event) = self.clipboard_rx.try_recv() {
-            self.clipboard_text = Some(event.text.clone());
+        // update clipboard contents
+        if let Ok(event) = self.clipboard_rx.try_recv()
+            && !self.monitoring_paused
+        {
+            if let Some(text) = event.text.as_ref() {
+                self.history.record_clipboard(text);
+            }
+            self.update_clipboard_text(event.text);
+            self.clipboard_html = event.html;
+            self.clipboard_rtf = event.rtf;
+            self.clipboard_image = event.image;
+            self.clipboard_files = event.files;
+            self.clipboard_texture = None;
             // if no hotkey is set, show window
             if self.config.hotkey.is_none() {
                 do_show = true;
@@ -332,21 +990,63 @@ impl UI {
         }
     }
 
-    fn on_action_response(&mut self, _ctx: &egui::Context) {
-        if let Ok(response) = self.action_response_rx.try_recv() {
-            // stop loading when response is received
-            self.is_loading = false;
-            self.current_action_label.clear();
+    fn on_action_response(&mut self, ctx: &egui::Context) {
+        for event in self.executor.poll() {
+            match event {
+                ai::ActionEvent::Chunk(..)
+                | ai::ActionEvent::Step(..)
+                | ai::ActionEvent::Cached(..)
+                | ai::ActionEvent::Attempt(..) => {}
+                // shown next to the title until the stats say otherwise
+                ai::ActionEvent::Warning(_, warning) => {
+                    if !self.stats.warnings.contains(&warning) {
+                        self.stats.warnings.push(warning);
+                    }
+                }
+                ai::ActionEvent::Response(id, response, do_paste) => {
+                    // go back to the main view when the job being shown completes
+                    if self.focused_job == Some(id) {
+                        self.focused_job = None;
+                    }
+                    // the completion was recorded by now
+                    self.refresh_stats();
+                    let label = self.executor.job(id).map(|job| job.label.clone());
+                    if let Some(label) = label.as_ref() {
+                        self.history.record_action(label, &response);
+                    }
 
-            match response {
-                ai::ActionEvent::Response(response, do_paste) => {
-                    self.clipboard_text = Some(response.clone());
-                    if do_paste && let Err(e) = clipboard::set_clipboard_text(response) {
-                        self.error_message = format!("❌ Failed to paste to clipboard: {}", e);
-                        self.show_error_modal = true;
+                    // held back until the user accepts it
+                    if let Some(input) = self.pending_reviews.remove(&id)
+                        && let Some(label) = label
+                    {
+                        let original = self
+                            .config
+                            .action_index(&label)
+                            .and_then(|index| input.text_as(&self.config.actions[index].format))
+                            .unwrap_or_default();
+                        self.review = Some(review::Review {
+                            job: id,
+                            label,
+                            input,
+                            original,
+                            response,
+                            editing: false,
+                        });
+                        // actions run from the control socket are reviewed too
+                        if !self.window_visible {
+                            self.show_window(ctx);
+                        }
+                        continue;
                     }
+
+                    self.paste_response(label.as_deref().unwrap_or_default(), response, do_paste);
                 }
-                ai::ActionEvent::Error(error) => {
+                ai::ActionEvent::Error(id, error) => {
+                    if self.focused_job == Some(id) {
+                        self.focused_job = None;
+                    }
+                    self.pending_reviews.remove(&id);
+
                     self.error_message = format!("❌ {}", error);
                     self.show_error_modal = true;
                 }
@@ -354,13 +1054,53 @@ impl UI {
         }
     }
 
-    fn on_tray_menu_event(&mut self) {
-        if let Ok(event) = MenuEvent::receiver().try_recv() {
-            if event.id == self.tray.reload_menu_item.id()
-                && let Err(e) = tray::reload_config()
-            {
-                self.error_message = format!("❌ Failed to reload config: {}", e);
+    fn paste_response(&mut self, label: &str, response: String, do_paste: bool) {
+        // what's really in the clipboard, it's not tracked while monitoring is paused
+        let before = clipboard::get_clipboard()
+            .ok()
+            .and_then(|clipboard| clipboard.text)
+            .or_else(|| self.clipboard_text.clone());
+
+        self.set_clipboard_text(response.clone());
+        if !do_paste {
+            return;
+        }
+        match clipboard::set_clipboard_text(response.clone()) {
+            Ok(()) => {
+                if let Some(before) = before
+                    && before != response
+                {
+                    self.undo_stack.push(label, before, response);
+                    self.update_undo_menu();
+                }
+            }
+            Err(e) => {
+                self.error_message = format!("❌ Failed to paste to clipboard: {}", e);
                 self.show_error_modal = true;
+            }
+        }
+    }
+
+    fn on_config_change(&mut self, ctx: &egui::Context) {
+        // several writes in a row only need one reload
+        if self.config_rx.try_iter().count() > 0 {
+            self.reload_config(ctx);
+        }
+    }
+
+    fn on_tray_menu_event(&mut self, ctx: &egui::Context) {
+        if let Ok(event) = MenuEvent::receiver().try_recv() {
+            if event.id == self.tray.reload_menu_item.id() {
+                self.reload_config(ctx);
+            } else if event.id == self.tray.undo_menu_item.id() {
+                self.undo();
+            } else if event.id == self.tray.redo_menu_item.id() {
+                self.redo();
+            } else if event.id == self.tray.stats_menu_item.id() {
+                if !self.show_stats {
+                    self.toggle_stats();
+                }
+                self.show_window(ctx);
             } else if event.id == self.tray.quit_menu_item.id() {
                 std::process::exit(0)
             } else if event.id == self.tray.configure_menu_item.id()
@@ -373,9 +1113,25 @@ impl UI {
     }
 
     fn on_esc_pressed(&mut self, ctx: &egui::Context) {
-        // handle escape key to hide window
+        // handle escape key to cancel the running job, go back to the main view or hide window
         if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
-            self.hide_window(ctx);
+            if self.args_form.is_some() {
+                self.args_form = None;
+            } else if let Some(id) = self.focused_job {
+                if self.is_loading() {
+                    self.cancel_job(id);
+                } else {
+                    self.focused_job = None;
+                }
+            } else if self.review.is_some() {
+                self.review = None;
+            } else if self.show_history {
+                self.close_history();
+            } else if self.show_stats {
+                self.show_stats = false;
+            } else {
+                self.hide_window(ctx);
+            }
         }
     }
 
@@ -404,7 +1160,10 @@ impl UI {
 
 impl eframe::App for UI {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.on_tray_menu_event();
+        self.on_tray_menu_event(ctx);
+        self.on_config_change(ctx);
+        #[cfg(unix)]
+        self.on_control_request(ctx);
         self.on_clibboard_change_or_hotkey(ctx);
         self.on_esc_pressed(ctx);
         self.on_keypress(ctx);
@@ -418,6 +1177,7 @@ impl eframe::App for UI {
         ctx.set_pixels_per_point(ui_scale);
 
         self.show_error_modal(ctx);
+        self.show_args_form(ctx);
 
         // always request repaint to ensure we process channel messages
         ctx.request_repaint();
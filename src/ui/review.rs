@@ -0,0 +1,126 @@
+use eframe::egui::{self, text::LayoutJob};
+use similar::{ChangeTag, TextDiff};
+
+use super::UI;
+use crate::ai;
+
+// a response waiting to be accepted before it's pasted
+pub(super) struct Review {
+    pub job: ai::JobId,
+    // label of the action that produced it, used to retry
+    pub label: String,
+    pub input: ai::ActionInput,
+    // the text the action worked on
+    pub original: String,
+    pub response: String,
+    pub editing: bool,
+}
+
+// word level diff of the original text and the response, removed words are struck
+// through and added ones highlighted
+fn diff_layout(ui: &egui::Ui, original: &str, response: &str) -> LayoutJob {
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let text_color = ui.visuals().text_color();
+    let removed_color = ui.visuals().error_fg_color;
+    let added_color = if ui.visuals().dark_mode {
+        egui::Color32::LIGHT_GREEN
+    } else {
+        egui::Color32::DARK_GREEN
+    };
+
+    let mut job = LayoutJob::default();
+    for change in TextDiff::from_words(original, response).iter_all_changes() {
+        let format = match change.tag() {
+            ChangeTag::Equal => egui::TextFormat::simple(font_id.clone(), text_color),
+            ChangeTag::Delete => egui::TextFormat {
+                strikethrough: egui::Stroke::new(1.0, removed_color),
+                ..egui::TextFormat::simple(font_id.clone(), removed_color)
+            },
+            ChangeTag::Insert => egui::TextFormat {
+                underline: egui::Stroke::new(1.0, added_color),
+                ..egui::TextFormat::simple(font_id.clone(), added_color)
+            },
+        };
+        job.append(change.value(), 0.0, format);
+    }
+    job.wrap.max_width = ui.available_width();
+    job
+}
+
+impl UI {
+    pub(super) fn render_review(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let Some(review) = self.review.as_mut() else {
+            return;
+        };
+
+        let mut accept = false;
+        let mut retry = false;
+        let mut discard = false;
+        ui.horizontal(|ui| {
+            ui.label(format!("📝 {}", review.label));
+            accept = ui
+                .button(if review.editing {
+                    "Accept"
+                } else {
+                    "[Enter] Accept"
+                })
+                .clicked()
+                || (!review.editing && ctx.input(|i| i.key_pressed(egui::Key::Enter)));
+            if ui
+                .button(if review.editing { "Diff" } else { "Edit" })
+                .clicked()
+            {
+                review.editing = !review.editing;
+            }
+            retry = ui.button("Retry").clicked();
+            discard = ui.button("[Esc] Discard").clicked();
+        });
+
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height())
+            .show(ui, |ui| {
+                if review.editing {
+                    ui.add_sized(
+                        ui.available_size(),
+                        egui::TextEdit::multiline(&mut review.response),
+                    );
+                } else {
+                    let layout = diff_layout(ui, &review.original, &review.response);
+                    ui.add(egui::Label::new(layout).selectable(true));
+                }
+            });
+
+        if accept {
+            self.accept_review();
+        } else if retry {
+            self.retry_review();
+        } else if discard {
+            self.review = None;
+        }
+    }
+
+    fn accept_review(&mut self) {
+        if let Some(review) = self.review.take() {
+            self.paste_response(&review.label, review.response, true);
+        }
+    }
+
+    // run the action again on the same input, the new response is reviewed as well
+    fn retry_review(&mut self) {
+        let Some(review) = self.review.take() else {
+            return;
+        };
+        let Some(index) = self.config.action_index(&review.label) else {
+            self.error_message = format!("❌ No action labeled {}", review.label);
+            self.show_error_modal = true;
+            return;
+        };
+
+        // the same request would get the same cached response
+        let mut input = review.input;
+        input.refresh = true;
+        let id = self.executor.submit(&self.config.actions[index], &input);
+        self.pending_reviews.insert(id, input);
+        self.focused_job = Some(id);
+    }
+}
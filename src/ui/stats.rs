@@ -0,0 +1,112 @@
+use eframe::egui;
+
+use super::UI;
+use crate::ai;
+
+// the usage of the current day and month, as kept by the ledger
+#[derive(Default)]
+pub(super) struct Stats {
+    summary: Option<ai::UsageSummary>,
+    // budgets exceeded so far
+    pub warnings: Vec<String>,
+    // show the month instead of the day
+    month: bool,
+}
+
+impl UI {
+    // take the usage again, after a completion or when the budgets change
+    pub(super) fn refresh_stats(&mut self) {
+        let ledger = self.config.ledger();
+        match ledger.summary() {
+            Ok(summary) => {
+                self.stats.warnings = ledger
+                    .exceeded(&summary)
+                    .into_iter()
+                    .map(|(_, message)| message)
+                    .collect();
+                self.stats.summary = Some(summary);
+            }
+            Err(e) => eprintln!("could not read usage: {}", e),
+        }
+    }
+
+    pub(super) fn render_stats(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.selectable_label(!self.stats.month, "Today").clicked() {
+                self.stats.month = false;
+            }
+            if ui
+                .selectable_label(self.stats.month, "This month")
+                .clicked()
+            {
+                self.stats.month = true;
+            }
+        });
+        for warning in self.stats.warnings.iter() {
+            ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+        }
+
+        let Some(summary) = self.stats.summary.as_ref() else {
+            return;
+        };
+        let period = if self.stats.month {
+            &summary.month
+        } else {
+            &summary.today
+        };
+        if period.rows.is_empty() {
+            ui.weak("No completions yet.");
+            return;
+        }
+
+        let totals = &period.totals;
+        let mut total = format!(
+            "{} · {} requests · {} in / {} out",
+            ai::format_cost(totals.cost),
+            totals.requests,
+            ai::format_tokens(totals.input_tokens),
+            ai::format_tokens(totals.output_tokens)
+        );
+        if totals.unpriced > 0 {
+            total.push_str(&format!(" · {} without a price", totals.unpriced));
+        }
+        ui.label(total);
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .id_salt("stats_scroll")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                egui::Grid::new("stats_grid")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in ["Action", "Model", "Requests", "Tokens", "Latency", "Cost"] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+
+                        for row in period.rows.iter() {
+                            ui.label(&row.action);
+                            ui.label(&row.model).on_hover_text(&row.provider);
+                            ui.label(row.totals.requests.to_string());
+                            ui.label(format!(
+                                "{} / {}",
+                                ai::format_tokens(row.totals.input_tokens),
+                                ai::format_tokens(row.totals.output_tokens)
+                            ));
+                            ui.label(format!(
+                                "{:.1}s",
+                                row.totals.average_latency().as_secs_f32()
+                            ));
+                            if row.totals.unpriced == row.totals.requests {
+                                ui.weak("-");
+                            } else {
+                                ui.label(ai::format_cost(row.totals.cost));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
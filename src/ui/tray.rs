@@ -35,26 +35,6 @@ pub(crate) fn open_config_folder() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub(crate) fn reload_config() -> anyhow::Result<()> {
-    // restart the process to reload configuration
-    let current_exe = std::env::current_exe()?;
-    let mut command = std::process::Command::new(current_exe);
-    // add all original command line arguments if any
-    let args: Vec<String> = std::env::args().skip(1).collect();
-    command.args(&args);
-    // add the start delay argument to ensure the single instance is released
-    if !args.contains(&"--start-delay".to_string()) {
-        command.arg("--start-delay").arg("500");
-    }
-    // go go go!
-    command.spawn()?;
-
-    std::process::exit(0);
-
-    #[allow(unreachable_code)]
-    Ok(())
-}
-
 fn load_icons() -> (tray_icon::Icon, tray_icon::menu::Icon) {
     let (icon_rgba, icon_width, icon_height) = {
         let bytes = include_bytes!("../../assets/icon-256.png");
@@ -78,6 +58,10 @@ pub(crate) struct Tray {
     _icon: TrayIcon,
     pub configure_menu_item: MenuItem,
     pub reload_menu_item: MenuItem,
+    // disabled until there's something to undo or redo
+    pub undo_menu_item: MenuItem,
+    pub redo_menu_item: MenuItem,
+    pub stats_menu_item: MenuItem,
     pub quit_menu_item: MenuItem,
 }
 
@@ -85,6 +69,9 @@ pub(crate) fn build_tray_menu_icon() -> anyhow::Result<Tray> {
     let tray_menu = Menu::new();
     let configure_menu_item = MenuItem::new("Configure", true, None);
     let reload_menu_item = MenuItem::new("Reload Configuration", true, None);
+    let undo_menu_item = MenuItem::new("Undo", false, None);
+    let redo_menu_item = MenuItem::new("Redo", false, None);
+    let stats_menu_item = MenuItem::new("Usage Stats", true, None);
     let quit_menu_item = MenuItem::new("Quit", true, None);
     let (icon, menu_icon) = load_icons();
 
@@ -94,6 +81,11 @@ pub(crate) fn build_tray_menu_icon() -> anyhow::Result<Tray> {
         &configure_menu_item,
         &reload_menu_item,
         &PredefinedMenuItem::separator(),
+        &undo_menu_item,
+        &redo_menu_item,
+        &PredefinedMenuItem::separator(),
+        &stats_menu_item,
+        &PredefinedMenuItem::separator(),
         &PredefinedMenuItem::about(
             None,
             Some(AboutMetadata {
@@ -119,6 +111,9 @@ pub(crate) fn build_tray_menu_icon() -> anyhow::Result<Tray> {
         _icon: tray_icon,
         configure_menu_item,
         reload_menu_item,
+        undo_menu_item,
+        redo_menu_item,
+        stats_menu_item,
         quit_menu_item,
     })
 }
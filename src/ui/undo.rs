@@ -0,0 +1,119 @@
+use eframe::egui;
+
+use super::UI;
+use crate::clipboard;
+
+// how many transformations are kept for the session
+const MAX_TRANSFORMATIONS: usize = 50;
+
+// a response pasted to the clipboard, along with what it replaced
+struct Transformation {
+    label: String,
+    before: String,
+    after: String,
+}
+
+#[derive(Default)]
+pub(super) struct UndoStack {
+    undo: Vec<Transformation>,
+    redo: Vec<Transformation>,
+}
+
+impl UndoStack {
+    pub fn push(&mut self, label: &str, before: String, after: String) {
+        // a new transformation starts a new history
+        self.redo.clear();
+        self.undo.push(Transformation {
+            label: label.to_string(),
+            before,
+            after,
+        });
+        if self.undo.len() > MAX_TRANSFORMATIONS {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn undo_label(&self) -> Option<&str> {
+        self.undo.last().map(|t| t.label.as_str())
+    }
+
+    pub fn redo_label(&self) -> Option<&str> {
+        self.redo.last().map(|t| t.label.as_str())
+    }
+
+    // the text to restore to undo the last transformation
+    fn undo(&mut self) -> Option<String> {
+        let transformation = self.undo.pop()?;
+        let text = transformation.before.clone();
+        self.redo.push(transformation);
+        Some(text)
+    }
+
+    // the text to restore to apply the last undone transformation again
+    fn redo(&mut self) -> Option<String> {
+        let transformation = self.redo.pop()?;
+        let text = transformation.after.clone();
+        self.undo.push(transformation);
+        Some(text)
+    }
+}
+
+impl UI {
+    pub(super) fn undo(&mut self) {
+        if let Some(text) = self.undo_stack.undo() {
+            self.restore_clipboard(text);
+        }
+    }
+
+    pub(super) fn redo(&mut self) {
+        if let Some(text) = self.undo_stack.redo() {
+            self.restore_clipboard(text);
+        }
+    }
+
+    fn restore_clipboard(&mut self, text: String) {
+        self.set_clipboard_text(text.clone());
+        if let Err(e) = clipboard::set_clipboard_text(text) {
+            self.error_message = format!("❌ Failed to restore the clipboard: {}", e);
+            self.show_error_modal = true;
+        }
+        self.update_undo_menu();
+    }
+
+    // the tray items say what they would undo or redo
+    pub(super) fn update_undo_menu(&self) {
+        let undo = self.undo_stack.undo_label();
+        self.tray.undo_menu_item.set_enabled(undo.is_some());
+        self.tray.undo_menu_item.set_text(match undo {
+            Some(label) => format!("Undo {}", label),
+            None => "Undo".to_string(),
+        });
+
+        let redo = self.undo_stack.redo_label();
+        self.tray.redo_menu_item.set_enabled(redo.is_some());
+        self.tray.redo_menu_item.set_text(match redo {
+            Some(label) => format!("Redo {}", label),
+            None => "Redo".to_string(),
+        });
+    }
+
+    pub(super) fn render_undo_buttons(&mut self, ui: &mut egui::Ui) {
+        let redo = self.undo_stack.redo_label().map(str::to_string);
+        if ui
+            .add_enabled(redo.is_some(), egui::Button::new("↷"))
+            .on_hover_text(format!("Redo {} (Ctrl+Shift+Z)", redo.unwrap_or_default()))
+            .clicked()
+        {
+            self.redo();
+        }
+
+        let undo = self.undo_stack.undo_label().map(str::to_string);
+        if ui
+            .add_enabled(undo.is_some(), egui::Button::new("↶"))
+            .on_hover_text(format!("Undo {} (Ctrl+Z)", undo.unwrap_or_default()))
+            .clicked()
+        {
+            self.undo();
+        }
+    }
+}